@@ -0,0 +1,207 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::controller::{Context, Controller};
+use crate::game::{Direction, GameState, PaddleMovement, Phase, Side};
+use crate::rng::Rng;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// Ticks between the ball turning towards the AI and the AI reacting.
+    fn reaction_ticks(self) -> u32 {
+        match self {
+            Difficulty::Easy => 30,
+            Difficulty::Normal => 15,
+            Difficulty::Hard => 4,
+        }
+    }
+
+    /// Largest distance, in canvas units, between where the AI thinks the
+    /// ball will arrive and where it actually does.
+    fn prediction_error(self) -> f64 {
+        match self {
+            Difficulty::Easy => 25.0,
+            Difficulty::Normal => 12.0,
+            Difficulty::Hard => 4.0,
+        }
+    }
+
+    /// How the AI paddle moves, it only gets the full top speed on hard.
+    pub fn movement(self) -> PaddleMovement {
+        let max_speed = match self {
+            Difficulty::Easy => 1.0,
+            Difficulty::Normal => 1.5,
+            Difficulty::Hard => 2.0,
+        };
+        PaddleMovement {
+            max_speed,
+            ..PaddleMovement::default()
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        })
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!(
+                "unknown difficulty '{s}', expected easy, normal or hard"
+            )),
+        }
+    }
+}
+
+/// Computer opponent. It works out where the ball will cross its paddle,
+/// bouncing off the walls on the way, and heads there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ai {
+    pub side: Side,
+    pub difficulty: Difficulty,
+    rng: Rng,
+    // ticks since the ball last turned towards us
+    approaching_for: Option<u32>,
+    // where we're aiming the paddle centre for this approach
+    target: Option<f64>,
+    // error picked once per approach so the paddle doesn't jitter
+    error: f64,
+}
+
+impl Ai {
+    pub fn new(side: Side, difficulty: Difficulty, game: &GameState) -> Ai {
+        Ai {
+            side,
+            difficulty,
+            rng: game.rng_stream(rng_stream(side)),
+            approaching_for: None,
+            target: None,
+            error: 0.0,
+        }
+    }
+
+    fn decide(&mut self, game: &GameState) -> Direction {
+        let player = game.player(self.side);
+        let paddle = player.paddle;
+        let ball = game.ball;
+        let heading = match self.side {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        };
+        let approaching = game.phase == Phase::Playing && ball.vx * heading > 0.0;
+
+        if approaching {
+            let ticks = self.approaching_for.map_or(0, |t| t + 1);
+            if ticks == 0 {
+                let max = self.difficulty.prediction_error();
+                self.error = self.rng.range(-max, max);
+                self.target = None;
+            }
+            self.approaching_for = Some(ticks);
+            if ticks >= self.difficulty.reaction_ticks() {
+                self.target = predict_intercept(game, self.side).map(|y| y + self.error);
+            }
+        } else {
+            // drift back to the middle while the ball is going away
+            self.approaching_for = None;
+            self.target = Some(game.height / 2.0);
+        }
+
+        let Some(target) = self.target else {
+            return Direction::Na;
+        };
+        let center = paddle.y + paddle.height / 2.0;
+        let distance = target - center;
+        // coast if we'd overshoot anyway while braking
+        let stopping = player.velocity.powi(2) / (2.0 * player.movement.friction);
+        if distance.abs() <= stopping.max(paddle.height / 4.0) {
+            Direction::Na
+        } else if distance > 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        }
+    }
+}
+
+impl Controller for Ai {
+    fn intent(&mut self, ctx: &Context) -> Direction {
+        self.decide(ctx.game)
+    }
+
+    fn name(&self) -> String {
+        format!("CPU ({})", self.difficulty)
+    }
+
+    fn movement(&self) -> PaddleMovement {
+        self.difficulty.movement()
+    }
+
+    fn reset(&mut self, game: &GameState) {
+        self.rng = game.rng_stream(rng_stream(self.side));
+        self.approaching_for = None;
+        self.target = None;
+    }
+}
+
+// Each side aims with its own numbers, so two AIs don't mirror each other
+fn rng_stream(side: Side) -> u64 {
+    match side {
+        Side::Left => 1,
+        Side::Right => 2,
+    }
+}
+
+/// Height at which the ball centre will reach the face of the paddle on
+/// `side`, following bounces off the floor and ceiling. Spin is ignored.
+pub fn predict_intercept(game: &GameState, side: Side) -> Option<f64> {
+    let ball = game.ball;
+    let paddle = game.player(side).paddle;
+    let face = match side {
+        Side::Left => paddle.x + paddle.width + ball.radius,
+        Side::Right => paddle.x - ball.radius,
+    };
+    if ball.vx == 0.0 {
+        return None;
+    }
+    let t = (face - ball.x) / ball.vx;
+    if t < 0.0 {
+        return None;
+    }
+
+    // unfold the bounces: the ball travels in a band of this height
+    let low = ball.radius;
+    let span = game.height - 2.0 * ball.radius;
+    if span <= 0.0 {
+        return Some(game.height / 2.0);
+    }
+    let y = (ball.y + ball.vy * t - low).rem_euclid(2.0 * span);
+    Some(low + if y > span { 2.0 * span - y } else { y })
+}
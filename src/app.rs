@@ -1,102 +1,204 @@
-use std::{fs::File, io::Write};
-use std::ops::Add;
+use std::{fs::File, io::Write, path::PathBuf};
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Rect},
+    layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Style, Stylize},
     symbols::border,
     symbols::Marker,
     text::{Line, Span, Text},
     widgets::{
-        canvas::{Canvas, Circle, Rectangle},
-        Block, Paragraph, Widget,
+        canvas::{self, Canvas, Circle, Rectangle},
+        Block, Clear, Paragraph, Widget,
     },
     DefaultTerminal, Frame,
 };
 use std::io;
-use crate::constants;
-use std::time::{Duration, Instant};
+use crate::ai::{Ai, Difficulty};
+use crate::cli::Args;
+use crate::config;
+use crate::controller::{Context, Controller, ControllerSpec};
+use crate::constants::{
+    CELL_ASPECT, MAX_TICKS_PER_FRAME, MIN_PLAYGROUND_WIDTH, NET_INPUT_DELAY, NET_PORT, PADDLE_WIDTH, TICK_RATE,
+};
+use crate::game::{
+    Ball, Direction, GameConfig, GameEvent, GameState, Inputs, Paddle, Player, Side,
+};
+use crate::input::HeldKeys;
+use crate::keymap::{can_bind, key_name, Action, Keymap};
+use crate::net::{self, Exchange, Host, Netplay, Remote, Watch};
+use crate::replay::Replay;
+use crate::rules::MatchRules;
+use crate::settings::{Setting, Settings};
+use crate::theme::{ColorDepth, Theme};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum CurrentScreen {
     StartMenu,
     InGame,
+    Paused,
+    Settings,
+    Keys,
+    GameOver,
+    Hosting,
+    Joining,
 }
 
-#[derive(PartialEq)]
-pub enum Direction {
-    Up,
-    Down,
-    Na,
-}
-
-pub struct Player {
-    pub paddle: Rectangle,
-    pub lifes: usize,
-    pub starting_lifes: usize,
-    pub direction: Direction,
-}
-
-impl Player {
-    pub fn new(x: f64, color: Color) -> Player {
-        Player {
-            lifes: 3,
-            starting_lifes: 3,
-            direction: Direction::Na,
-            paddle: Rectangle {
-                x,
-                y: 10.0,
-                width: 3.0,
-                height: 20.0,
-                color
-            }
-        }
-    }
-}
-
+#[derive(Clone, Copy, PartialEq)]
 pub enum CurrentSelection {
+    OnePlayer,
     NewGame,
+    HostGame,
+    JoinGame,
+    WatchGame,
+    Difficulty,
+    Settings,
+    Setting(Setting),
+    Bind(Action),
+    ResetKeys,
+    Back,
+    Resume,
+    Restart,
+    QuitToMenu,
+    Rematch,
+    MainMenu,
     Exit,
 }
 
 pub struct App {
     pub tick_count: u64,
-    pub marker: Marker,
     pub current_screen: CurrentScreen,
     pub current_selection: Option<CurrentSelection>,
     pub exit: bool,
-    pub ball: Circle,
-    pub playground: Rect,
-    vx: f64,
-    vy: f64,
-    pub p1: Player,
-    pub p2: Player,
+    pub show_debug: bool,
+    pub game: GameState,
+    // state before the last tick, used to interpolate between ticks when rendering
+    previous: GameState,
+    // how far we are between `previous` and `game`, in 0.0..1.0
+    alpha: f64,
+    pub inputs: Inputs,
+    held_keys: HeldKeys,
+    // whoever is steering each paddle
+    p1_controller: Box<dyn Controller>,
+    p2_controller: Box<dyn Controller>,
+    // p2 is played by the AI
+    pub single_player: bool,
+    pub difficulty: Difficulty,
+    // picked on the settings screen, for the next match, and saved here
+    settings: Settings,
+    // `--rules`, which is never saved, until the rules are changed in settings
+    rules_override: Option<MatchRules>,
+    config_path: Option<PathBuf>,
+    // whether anything was changed since the settings screen was opened
+    settings_edited: bool,
+    // waiting for the key to bind to this
+    rebinding: Option<Action>,
+    // columns and rows, as of the last resize
+    terminal_size: (u16, u16),
+    // themes are toned down to what the terminal can show
+    color_depth: ColorDepth,
+    // how long a tick of the current match lasts
+    tick_rate: Duration,
+    // the settings screen shows them off on this, and goes back here
+    preview: Option<Preview>,
+    settings_from: CurrentScreen,
+    // --record: where to save matches and the one being played
+    record_path: Option<PathBuf>,
+    recording: Option<Replay>,
+    // --replay: the match being played back
+    playback: Option<Playback>,
+    // waiting for someone to join, and the match against them once they have
+    host: Option<Host>,
+    net: Option<Netplay>,
+    // someone else's match we're watching
+    watch: Option<Watch>,
+    // what's typed on the join screen, and whether it's to watch or to play
+    join_address: String,
+    watching: bool,
+    // ticks between pressing a key and the paddle reacting, over the network
+    input_delay: u64,
+    // why the last network match ended or couldn't start
+    net_status: Option<String>,
     pub logfile: File,
 }
 
+struct Playback {
+    replay: Replay,
+    // first tick whose checksum didn't match the recording
+    diverged: Option<u64>,
+}
+
+// Two computer players going at it with the settings being edited
+struct Preview {
+    game: GameState,
+    previous: GameState,
+    p1: Ai,
+    p2: Ai,
+}
+
 impl App {
     
-    pub fn new() -> App {
+    pub fn new(
+        args: Args,
+        settings: Settings,
+        config_path: Option<PathBuf>,
+        reports_key_release: bool,
+    ) -> App {
         let logfile = File::create("app_log.txt").expect("could not open file");
+        let mut config = GameConfig::default();
+        settings.apply(&mut config);
+        // the command line wins over the config file, for this run only
+        if let Some(rules) = args.rules {
+            config.rules = rules;
+        }
+        let mut game = GameState::new(config, args.seed.unwrap_or_else(random_seed));
+        let p1_spec = args.p1.unwrap_or(ControllerSpec::Keyboard);
+        let p2_spec = args.p2.unwrap_or(ControllerSpec::Keyboard);
+        let p1_controller = p1_spec.build(Side::Left, &game, &settings.keys);
+        let p2_controller = p2_spec.build(Side::Right, &game, &settings.keys);
+        game.set_movement(Side::Left, p1_controller.movement());
+        game.set_movement(Side::Right, p2_controller.movement());
+        let difficulty = match p2_spec {
+            ControllerSpec::Ai(difficulty) => difficulty,
+            _ => Difficulty::default(),
+        };
         App {
-            playground: Rect::new(0, 0, 200, 100),
-            vx: 1.0,
-            vy: 1.0,
-            ball: Circle {
-                x: 10.0,
-                y: 10.0,
-                radius: 5.0,
-                color: Color::Cyan,
-            },
+            recording: args.record.as_ref().map(|_| Replay::record(&game)),
+            record_path: args.record,
+            playback: None,
+            host: None,
+            net: None,
+            watch: None,
+            join_address: format!("127.0.0.1:{NET_PORT}"),
+            watching: false,
+            input_delay: args.input_delay.unwrap_or(NET_INPUT_DELAY),
+            net_status: None,
+            previous: game.clone(),
+            alpha: 0.0,
+            game,
+            inputs: Inputs::default(),
+            held_keys: HeldKeys::new(reports_key_release),
+            p1_controller,
+            p2_controller,
+            single_player: matches!(p2_spec, ControllerSpec::Ai(_)),
+            difficulty,
+            tick_rate: settings.tick_rate,
+            settings,
+            rules_override: args.rules,
+            config_path,
+            settings_edited: false,
+            rebinding: None,
+            terminal_size: (0, 0),
+            color_depth: ColorDepth::detect(),
+            preview: None,
+            settings_from: CurrentScreen::StartMenu,
             tick_count: 0,
-            marker: Marker::Dot,
             current_screen: CurrentScreen::StartMenu,
-            current_selection: Some(CurrentSelection::NewGame),
+            current_selection: Some(CurrentSelection::OnePlayer),
             exit: false,
-            p1: Player::new(10.0,Color::Yellow),
-            p2: Player::new(190.0,Color::Green),
+            show_debug: false,
             logfile,
         }
     }
@@ -108,104 +210,362 @@ impl App {
     fn on_tick(&mut self) {
         self.tick_count += 1;
 
-        if self.current_screen == CurrentScreen::StartMenu {
+        // the host takes spectators all through the match
+        if self.host.is_some() {
+            self.accept_guest();
+        }
+        if self.current_screen == CurrentScreen::Settings {
+            self.step_preview();
+        }
+        if self.current_screen != CurrentScreen::InGame {
+            return;
+        }
+        if let Some(playback) = &self.playback {
+            playback.replay.apply_resizes(&mut self.game);
+        }
+        self.previous.clone_from(&self.game);
+        if self.watch.is_some() {
+            self.follow_match();
             return;
         }
-        assert!(self.current_screen == CurrentScreen::InGame);
-        // Move Paddles
-        match self.p1.direction {
-            Direction::Down => self.p1.paddle.y = f64::max(self.p1.paddle.y - 1.0, 0.0),
-            Direction::Up => self.p1.paddle.y = f64::min(self.p1.paddle.y + 1.0, 100.0 - self.p1.paddle.height),
-            Direction::Na => (),
+        let mut ctx = Context {
+            game: &self.game,
+            side: Side::Left,
+            keys: &self.held_keys,
+            now: Instant::now(),
+        };
+        self.inputs = match &mut self.net {
+            Some(net) => {
+                ctx.side = net.side;
+                let controller = match net.side {
+                    Side::Left => &mut self.p1_controller,
+                    Side::Right => &mut self.p2_controller,
+                };
+                let local = net
+                    .needs_input(self.game.tick)
+                    .then(|| controller.intent(&ctx));
+                match net.exchange(&mut self.game, local) {
+                    Exchange::Ready(inputs) => inputs,
+                    Exchange::Waiting => return,
+                    Exchange::Finished => {
+                        self.previous.clone_from(&self.game);
+                        self.end_match();
+                        return;
+                    }
+                    Exchange::Closed(reason) => {
+                        self.leave_netplay(Some(reason));
+                        return;
+                    }
+                }
+            }
+            None => {
+                let p1 = self.p1_controller.intent(&ctx);
+                ctx.side = Side::Right;
+                let p2 = self.p2_controller.intent(&ctx);
+                Inputs { p1, p2 }
+            }
+        };
+        // network matches keep their own log, see `Netplay::log`
+        let mut recording = match self.net {
+            Some(_) => None,
+            None => self.recording.as_mut(),
+        };
+        if let Some(recording) = &mut recording {
+            recording.record_inputs(self.inputs);
+        }
+        let events = self.game.step(self.inputs);
+        if let Some(recording) = recording {
+            recording.record_state(&self.game);
+        }
+        if let Some(playback) = &mut self.playback {
+            if playback.diverged.is_none() {
+                playback.diverged = playback.replay.check(&self.game);
+            }
+            // out of recorded inputs, the players take it from here
+            if self.game.tick == playback.replay.inputs.len() as u64 {
+                self.hand_to_keyboard();
+            }
+        }
+        for event in events {
+            match event {
+                // over the network this is only a prediction, wait for `Exchange::Finished`
+                GameEvent::GameOver { .. } if self.net.is_none() => self.end_match(),
+                // the ball jumped back to the middle, don't smear it across the screen
+                GameEvent::PointLost(_) => self.previous.clone_from(&self.game),
+                _ => {}
+            }
         }
+    }
 
-        match self.p2.direction {
-            Direction::Down => self.p2.paddle.y = f64::max(self.p2.paddle.y - 1.0, 0.0),
-            Direction::Up => self.p2.paddle.y = f64::min(self.p2.paddle.y + 1.0, 100.0 - self.p2.paddle.height),
-            Direction::Na => (),
+    fn end_match(&mut self) {
+        self.current_screen = CurrentScreen::GameOver;
+        self.current_selection = Some(self.menu_options()[0]);
+        if let (Some(net), Some(recording)) = (&self.net, &mut self.recording) {
+            recording.clone_from(&net.log);
         }
+        self.save_recording();
+    }
 
-        // bounce the ball by flipping the velocity vector
-        let ball = &self.ball;
-        let playground = self.playground;
+    /// Watch a recorded match. It plays out exactly as it did when recorded,
+    /// after which both paddles are handed to the keyboard.
+    pub fn play_replay(&mut self, replay: Replay) {
+        self.game = replay.start();
+        self.previous.clone_from(&self.game);
+        self.p1_controller = Box::new(replay.controller(Side::Left));
+        self.p2_controller = Box::new(replay.controller(Side::Right));
+        self.playback = Some(Playback {
+            replay,
+            diverged: None,
+        });
+        self.current_screen = CurrentScreen::InGame;
+    }
 
-        if ball.x - ball.radius < f64::from(playground.left()) {
-            self.p1.lifes -= 1;
-            if self.p1.lifes < 1 {
-                // P2 Wins!
-                self.exit = true; // TODO Implement P2 Wins!
-            } else {
-                // Reset game  and wait 1000ms
-                self.vx = -self.vx;  // TODO: implement reset
+    // Step the match we're watching with whatever the host has confirmed
+    fn follow_match(&mut self) {
+        let Some(watch) = &mut self.watch else {
+            return;
+        };
+        let ticks = match watch.take() {
+            Ok(ticks) => ticks,
+            Err(reason) => {
+                self.leave_netplay(Some(reason));
+                return;
+            }
+        };
+        for inputs in ticks {
+            self.inputs = inputs;
+            for event in self.game.step(inputs) {
+                match event {
+                    GameEvent::GameOver { .. } => self.end_match(),
+                    GameEvent::PointLost(_) => self.previous.clone_from(&self.game),
+                    _ => {}
+                }
             }
         }
+    }
 
-        if ball.x + ball.radius > f64::from(playground.right()) {
-            self.p2.lifes -= 1;
-            if self.p2.lifes < 1 {
-                // P1 Wins!
-                self.exit = true; // TODO Implement p1 wins!
-            } else {
-                // reset game and wait 1000ms
-                self.vx = -self.vx;  // TODO: implement reset
-
+    // Poll for someone joining the match we're hosting
+    fn accept_guest(&mut self) {
+        let Some(host) = &self.host else {
+            return;
+        };
+        let guest = match host.accept() {
+            Ok(Some(guest)) => guest,
+            Ok(None) => return,
+            // keep waiting for someone who can play with us
+            Err(err) => {
+                self.net_status = Some(err.to_string());
+                return;
+            }
+        };
+        match (&mut self.net, guest.watching) {
+            (None, false) => match guest.play(&self.game) {
+                Ok((net, game)) => self.start_netplay(net, game),
+                Err(err) => self.net_status = Some(format!("A player could not join: {err}")),
+            },
+            (Some(net), true) => {
+                if let Err(err) = net.add_spectator(guest) {
+                    let _ = writeln!(self.logfile, "a spectator could not join: {err}");
+                }
             }
+            (None, true) => guest.reject("the match hasn't started yet"),
+            (Some(_), false) => guest.reject("the match is full, watch it instead"),
         }
-        
+    }
+
+    fn join_game(&mut self) {
+        let address = self.join_address.trim();
+        let joined = if self.watching {
+            net::watch(address).map(|(watch, game)| self.start_watching(watch, game))
+        } else {
+            net::join(address, self.input_delay).map(|(net, game)| self.start_netplay(net, game))
+        };
+        if let Err(err) = joined {
+            self.net_status = Some(format!("Could not join: {err}"));
+        }
+    }
 
-        // Implement paddle bounce
-        if (ball.x - ball.radius < f64::from(self.p1.paddle.x)) && 
-        (self.p1.paddle.y < ball.y && ball.y < self.p1.paddle.y + self.p1.paddle.height) {
-            self.vx = -self.vx;
+    fn start_netplay(&mut self, net: Netplay, game: GameState) {
+        let remote: Box<dyn Controller> = Box::new(Remote::new("Opponent"));
+        let local = Box::new(self.settings.keyboard(net.side));
+        match net.side {
+            Side::Left => self.set_players(game, local, remote),
+            Side::Right => self.set_players(game, remote, local),
         }
-        if ball.x + ball.radius > f64::from(self.p2.paddle.x) &&
-        (self.p2.paddle.y < ball.y && ball.y < self.p2.paddle.y + self.p1.paddle.height) {
-            self.vx = -self.vx;
+        if self.recording.is_some() {
+            self.save_recording();
+            self.recording = Some(Replay::record(&self.game));
         }
-        
-        if ball.y - ball.radius < f64::from(playground.top())
-            || ball.y + ball.radius > f64::from(playground.bottom())
-        {
-            self.vy = -self.vy;
+        self.net = Some(net);
+        self.net_status = None;
+        self.current_screen = CurrentScreen::InGame;
+    }
+
+    fn start_watching(&mut self, watch: Watch, game: GameState) {
+        self.set_players(
+            game,
+            Box::new(Remote::new("Player 1")),
+            Box::new(Remote::new("Player 2")),
+        );
+        self.watch = Some(watch);
+        self.net_status = None;
+        self.current_screen = CurrentScreen::InGame;
+        if self.game.winner.is_some() {
+            self.end_match();
+        }
+    }
+
+    // Take over a match set up elsewhere. Unlike `set_controller` this keeps
+    // the paddle movement the match came with.
+    fn set_players(&mut self, game: GameState, p1: Box<dyn Controller>, p2: Box<dyn Controller>) {
+        self.game = game;
+        self.p1_controller = p1;
+        self.p2_controller = p2;
+        self.single_player = false;
+        // both sides have to keep the same pace
+        self.tick_rate = TICK_RATE;
+        self.previous.clone_from(&self.game);
+        self.inputs = Inputs::default();
+        self.held_keys.clear();
+    }
+
+    // Playing or watching over the network
+    fn online(&self) -> bool {
+        self.net.is_some() || self.watch.is_some()
+    }
+
+    // Hang up, if we're playing over the network, and start over locally
+    fn leave_netplay(&mut self, reason: Option<String>) {
+        if !self.online() {
+            return;
+        }
+        self.reset_match();
+        self.net_status = reason;
+        self.current_screen = CurrentScreen::StartMenu;
+        self.current_selection = Some(CurrentSelection::OnePlayer);
+    }
+
+    // Both paddles back to the local players
+    fn hand_to_keyboard(&mut self) {
+        self.set_controller(Side::Left, Box::new(self.settings.keyboard(Side::Left)));
+        self.set_controller(Side::Right, Box::new(self.settings.keyboard(Side::Right)));
+        self.single_player = false;
+    }
+
+    // Not being able to save is no reason to stop playing, just log it
+    fn save_recording(&mut self) {
+        let (Some(path), Some(recording)) = (&self.record_path, &self.recording) else {
+            return;
+        };
+        if recording.inputs.is_empty() {
+            return;
+        }
+        if let Err(err) = recording.save(path) {
+            let _ = writeln!(self.logfile, "could not save {}: {err}", path.display());
         }
-        self.ball.x += self.vx;
-        self.ball.y += self.vy;
     }
 
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
-        let tick_rate = Duration::from_millis(16);
-        let mut last_tick = Instant::now();
+        let size = terminal.size()?;
+        self.resize(size.width, size.height);
+        let mut last_frame = Instant::now();
+        let mut accumulator = Duration::ZERO;
         while !self.exit {
+            let tick_rate = self.tick_rate();
+            let now = Instant::now();
+            accumulator += now - last_frame;
+            last_frame = now;
+
+            // Catch up on every tick we owe, but never more than
+            // MAX_TICKS_PER_FRAME in one go. Anything beyond that is dropped
+            // so the game slows down instead of freezing.
+            let mut ticks = 0;
+            while accumulator >= tick_rate && !self.exit {
+                if ticks == MAX_TICKS_PER_FRAME {
+                    accumulator = Duration::ZERO;
+                    break;
+                }
+                self.on_tick();
+                accumulator -= tick_rate;
+                ticks += 1;
+            }
+            self.alpha = accumulator.as_secs_f64() / tick_rate.as_secs_f64();
+
             terminal.draw(|frame| self.draw(frame))?;
 
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+            let timeout = tick_rate.saturating_sub(accumulator + last_frame.elapsed());
             if event::poll(timeout)? {
                 self.handle_events()?;
-            }
-
-            if last_tick.elapsed() >= tick_rate {
-                self.on_tick();
-                last_tick = Instant::now();
+                // drain anything else that queued up during a slow frame
+                while event::poll(Duration::ZERO)? {
+                    self.handle_events()?;
+                }
             }
         }
+        self.save_recording();
         Ok(())
     }
 
     fn handle_events(&mut self) -> io::Result<()> {
         match event::read()? {
-            // it's important to check that the event is a key press event as
-            // crossterm also emits key release and repeat events on Windows.
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.logfile.write_all(b"Press event happened!\n")?;
-                self.handle_key_event(key_event)
+            Event::Key(key_event) => {
+                // paddles move for as long as their keys are held
+                if self.current_screen == CurrentScreen::InGame {
+                    self.held_keys.handle(key_event, Instant::now());
+                }
+                // it's important to check that the event is a key press event as
+                // crossterm also emits key release and repeat events on Windows.
+                if key_event.kind == KeyEventKind::Press {
+                    self.logfile.write_all(b"Press event happened!\n")?;
+                    self.handle_key_event(key_event)
+                }
             }
+            Event::Resize(columns, rows) => self.resize(columns, rows),
+            Event::FocusLost => self.pause(),
             _ => {}
         };
         Ok(())
     }
     
+    // Fit the playground to the canvas so the ball stays round. The height is
+    // fixed, the width follows the aspect ratio of the area inside the border.
+    fn resize(&mut self, columns: u16, rows: u16) {
+        self.terminal_size = (columns, rows);
+        // a replay or network match keeps the playground it started with, the
+        // canvas stretches instead
+        if self.playback.is_some() || self.online() {
+            return;
+        }
+        let columns = f64::from(columns.saturating_sub(2).max(1));
+        let rows = f64::from(rows.saturating_sub(2).max(1));
+        let height = self.game.height;
+        let width = (height * columns * CELL_ASPECT / rows).max(MIN_PLAYGROUND_WIDTH);
+
+        // make sure a paddle is never thinner than one canvas pixel
+        let (pixels_x, _) = marker_resolution(self.settings.marker());
+        let pixel = width / (columns * pixels_x);
+        self.game.resize(width, PADDLE_WIDTH.max(pixel));
+        self.previous.clone_from(&self.game);
+        if let Some(recording) = &mut self.recording {
+            recording.record_resize(&self.game);
+        }
+    }
+
     fn handle_q_event(&mut self) {
-        if self.current_screen == CurrentScreen::InGame {
+        if self.current_screen == CurrentScreen::InGame && self.online() {
+            self.leave_netplay(None);
+        } else if matches!(
+            self.current_screen,
+            CurrentScreen::InGame | CurrentScreen::Paused
+        ) {
+            self.quit_to_menu();
+        } else if self.current_screen == CurrentScreen::Settings {
+            self.close_settings();
+        } else if self.current_screen == CurrentScreen::Keys {
+            self.close_keys();
+        } else if self.current_screen == CurrentScreen::Hosting {
+            self.host = None;
+            self.net_status = None;
             self.current_screen = CurrentScreen::StartMenu;
         } else {
             self.exit();
@@ -213,62 +573,478 @@ impl App {
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if self.current_screen == CurrentScreen::Joining {
+            self.handle_join_key_event(key_event);
+            return;
+        }
+        if let Some(action) = self.rebinding.take() {
+            // Esc can still be bound from the config file, keys that can't
+            // be saved can't be bound at all
+            if key_event.code != KeyCode::Esc && can_bind(key_event.code) {
+                self.settings.keys.bind(action, key_event.code);
+                self.settings_edited = true;
+            }
+            return;
+        }
+        if let KeyCode::Left | KeyCode::Right = key_event.code {
+            self.handle_adjust_event(key_event);
+        }
+        // a key bound twice does both, the settings warn about it
+        let actions: Vec<Action> = self.settings.keys.actions(key_event.code).collect();
+        for action in actions {
+            self.handle_action(action);
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.handle_q_event(),
+            Action::Debug => self.show_debug = !self.show_debug,
+            Action::Confirm => self.handle_selection_event(),
+            Action::P1Up | Action::P2Up => self.handle_direction_press_event(Direction::Up),
+            Action::P1Down | Action::P2Down => self.handle_direction_press_event(Direction::Down),
+            Action::Pause => self.handle_pause_event(),
+            Action::Back => self.handle_back_event(),
+            Action::Marker => self.handle_marker_event(),
+        }
+    }
+
+    // Try another marker without going through the settings
+    fn handle_marker_event(&mut self) {
+        if !matches!(
+            self.current_screen,
+            CurrentScreen::InGame | CurrentScreen::Paused
+        ) {
+            return;
+        }
+        self.settings.next_marker();
+        self.save_settings();
+        // paddles have to stay at least a pixel wide
+        let (columns, rows) = self.terminal_size;
+        self.resize(columns, rows);
+    }
+
+    fn handle_adjust_event(&mut self, key_event: KeyEvent) {
+        if let Some(CurrentSelection::Setting(setting)) = self.current_selection {
+            let step = if key_event.code == KeyCode::Left {
+                -1
+            } else {
+                1
+            };
+            self.adjust_setting(setting, step);
+        }
+    }
+
+    fn handle_pause_event(&mut self) {
+        match self.current_screen {
+            CurrentScreen::InGame => self.pause(),
+            CurrentScreen::Paused => self.resume(),
+            _ => {}
+        }
+    }
+
+    fn handle_back_event(&mut self) {
+        match self.current_screen {
+            CurrentScreen::InGame | CurrentScreen::Paused => self.handle_pause_event(),
+            CurrentScreen::Hosting | CurrentScreen::Settings | CurrentScreen::Keys => {
+                self.handle_q_event()
+            }
+            _ => {}
+        }
+    }
+
+    // Freeze the match. The other side of a network match would carry on
+    // without us, so those can't be paused.
+    fn pause(&mut self) {
+        if self.current_screen != CurrentScreen::InGame || self.online() {
+            return;
+        }
+        self.current_screen = CurrentScreen::Paused;
+        self.current_selection = Some(CurrentSelection::Resume);
+        // nothing is held anymore by the time we're back
+        self.held_keys.clear();
+        // stop interpolating, or the ball would jitter while frozen
+        self.previous.clone_from(&self.game);
+    }
+
+    fn resume(&mut self) {
+        self.current_screen = CurrentScreen::InGame;
+    }
+
+    fn open_settings(&mut self) {
+        self.settings_from = self.current_screen;
+        self.current_screen = CurrentScreen::Settings;
+        self.current_selection = Some(self.menu_options()[0]);
+        self.settings_edited = false;
+        self.preview = Some(self.preview());
+    }
+
+    // Back to where the settings were opened from. The paused match carries
+    // on as it was, a match waiting in the start menu starts over with the
+    // new settings.
+    fn close_settings(&mut self) {
+        self.preview = None;
+        self.current_screen = self.settings_from;
+        self.current_selection = Some(CurrentSelection::Settings);
+        if self.settings_edited {
+            self.save_settings();
+        }
+        self.rebind_keyboards();
+        let mut config = self.game.config;
+        self.apply_settings(&mut config);
+        let changed = config != self.game.config || self.settings.tick_rate != self.tick_rate;
+        if self.current_screen == CurrentScreen::StartMenu && changed {
+            self.reset_match();
+        }
+    }
+
+    // Back to the rest of the settings
+    fn close_keys(&mut self) {
+        self.current_screen = CurrentScreen::Settings;
+        self.current_selection = Some(CurrentSelection::Setting(Setting::Keys));
+    }
+
+    fn adjust_setting(&mut self, setting: Setting, step: isize) {
+        // changing the rules picks up from the ones being played
+        if let (Setting::Rules | Setting::Lives, Some(rules)) = (setting, self.rules_override) {
+            self.settings.rules = rules;
+            self.rules_override = None;
+        }
+        self.settings.adjust(setting, step);
+        self.settings_edited = true;
+        self.preview = Some(self.preview());
+    }
+
+    // The settings, with `--rules` on top if it still holds
+    fn apply_settings(&self, config: &mut GameConfig) {
+        self.settings.apply(config);
+        if let Some(rules) = self.rules_override {
+            config.rules = rules;
+        }
+    }
+
+    // Like recordings, settings that can't be saved are only worth a log line
+    fn save_settings(&mut self) {
+        let Some(path) = &self.config_path else {
+            return;
+        };
+        if let Err(err) = config::save(path, &self.settings) {
+            let _ = writeln!(self.logfile, "could not save {}: {err}", path.display());
+        }
+    }
+
+    // Keys only decide who moves which paddle, they can change mid-match
+    fn rebind_keyboards(&mut self) {
+        for side in [Side::Left, Side::Right] {
+            if self.controller(side).key_hint().is_some() {
+                self.set_controller(side, Box::new(self.settings.keyboard(side)));
+            }
+        }
+    }
+
+    fn preview(&self) -> Preview {
+        let mut config = self.game.config;
+        self.apply_settings(&mut config);
+        let mut game = GameState::new(config, self.game.seed);
+        // skip the countdown, there's nothing to see before the serve
+        while game.serve_countdown().is_some() {
+            game.step(Inputs::default());
+        }
+        Preview {
+            previous: game.clone(),
+            p1: Ai::new(Side::Left, Difficulty::Hard, &game),
+            p2: Ai::new(Side::Right, Difficulty::Hard, &game),
+            game,
+        }
+    }
+
+    fn step_preview(&mut self) {
+        let Some(preview) = &mut self.preview else {
+            return;
+        };
+        let mut ctx = Context {
+            game: &preview.game,
+            side: Side::Left,
+            keys: &self.held_keys,
+            now: Instant::now(),
+        };
+        let p1 = preview.p1.intent(&ctx);
+        ctx.side = Side::Right;
+        let p2 = preview.p2.intent(&ctx);
+        preview.previous.clone_from(&preview.game);
+        for event in preview.game.step(Inputs { p1, p2 }) {
+            match event {
+                GameEvent::PointLost(_) => preview.previous.clone_from(&preview.game),
+                GameEvent::GameOver { .. } => {
+                    preview.game.reset();
+                    preview.p1.reset(&preview.game);
+                    preview.p2.reset(&preview.game);
+                    preview.previous.clone_from(&preview.game);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The settings screen runs its preview at the tick rate being picked
+    fn tick_rate(&self) -> Duration {
+        match self.current_screen {
+            CurrentScreen::Settings => self.settings.tick_rate,
+            _ => self.tick_rate,
+        }
+    }
+
+    // Back to the start menu, leaving the match to pick up where it was
+    fn quit_to_menu(&mut self) {
+        self.held_keys.clear();
+        self.current_screen = CurrentScreen::StartMenu;
+        // Enter carries on with the same match
+        self.current_selection = Some(if self.single_player {
+            CurrentSelection::OnePlayer
+        } else {
+            CurrentSelection::NewGame
+        });
+        if self.playback.is_some() {
+            self.hand_to_keyboard();
+            self.reset_match();
+        }
+    }
+
+    // The join screen is a text field, so letters are typed rather than
+    // acted on
+    fn handle_join_key_event(&mut self, key_event: KeyEvent) {
         match key_event.code {
-            KeyCode::Char('q') => self.handle_q_event(),
-            KeyCode::Enter => self.handle_selection_event(),
-            KeyCode::Up => self.handle_direction_press_event(key_event),
-            KeyCode::Down => self.handle_direction_press_event(key_event),
-            KeyCode::Char('w') => self.handle_direction_press_event(key_event),
-            KeyCode::Char('s') => self.handle_direction_press_event(key_event),
+            KeyCode::Char(c) if self.join_address.len() < 64 => self.join_address.push(c),
+            KeyCode::Backspace => {
+                self.join_address.pop();
+            }
+            KeyCode::Enter => self.join_game(),
+            KeyCode::Esc => self.current_screen = CurrentScreen::StartMenu,
             _ => {}
         }
     }
     fn handle_selection_event(&mut self) {
         match self.current_selection {
-            Some(CurrentSelection::NewGame) => self.current_screen = CurrentScreen::InGame,
+            Some(CurrentSelection::OnePlayer) => self.start_game(true),
+            Some(CurrentSelection::NewGame) => self.start_game(false),
+            Some(CurrentSelection::HostGame) => match Host::listen(NET_PORT, self.input_delay) {
+                Ok(host) => {
+                    // the match is set up for two people at keyboards
+                    self.hand_to_keyboard();
+                    self.host = Some(host);
+                    self.net_status = None;
+                    self.current_screen = CurrentScreen::Hosting;
+                }
+                Err(err) => {
+                    self.net_status = Some(format!("Could not host on port {NET_PORT}: {err}"))
+                }
+            },
+            Some(CurrentSelection::JoinGame) => {
+                self.watching = false;
+                self.net_status = None;
+                self.current_screen = CurrentScreen::Joining;
+            }
+            Some(CurrentSelection::WatchGame) => {
+                self.watching = true;
+                self.net_status = None;
+                self.current_screen = CurrentScreen::Joining;
+            }
+            Some(CurrentSelection::Difficulty) => {
+                self.difficulty = self.difficulty.next();
+                if self.single_player {
+                    self.set_opponent(true);
+                }
+            }
+            Some(CurrentSelection::Settings) => self.open_settings(),
+            Some(CurrentSelection::Setting(Setting::Keys)) => {
+                self.current_screen = CurrentScreen::Keys;
+                self.current_selection = Some(self.menu_options()[0]);
+            }
+            Some(CurrentSelection::Setting(setting)) => self.adjust_setting(setting, 1),
+            Some(CurrentSelection::Bind(action)) => self.rebinding = Some(action),
+            Some(CurrentSelection::ResetKeys) => {
+                self.settings.keys = Keymap::default();
+                self.settings_edited = true;
+            }
+            Some(CurrentSelection::Back) => self.handle_q_event(),
+            Some(CurrentSelection::Resume) => self.resume(),
+            Some(CurrentSelection::Restart) => {
+                // a replay starts over from its first tick
+                match self.playback.take() {
+                    Some(playback) => self.play_replay(playback.replay),
+                    None => self.reset_match(),
+                }
+                self.current_screen = CurrentScreen::InGame;
+            }
+            Some(CurrentSelection::QuitToMenu) => self.quit_to_menu(),
+            Some(CurrentSelection::Rematch) => {
+                self.reset_match();
+                self.current_screen = CurrentScreen::InGame;
+            }
+            Some(CurrentSelection::MainMenu) => {
+                self.reset_match();
+                self.current_screen = CurrentScreen::StartMenu;
+                self.current_selection = Some(CurrentSelection::OnePlayer);
+            }
             Some(CurrentSelection::Exit) => {
                 self.current_selection = None;
                 self.exit();
             }
-            _ => {}
+            None => {}
+        }
+    }
+
+    // Continue the match in progress, unless it was played with a different
+    // number of players
+    fn start_game(&mut self, single_player: bool) {
+        if single_player != self.single_player {
+            self.set_opponent(single_player);
+        }
+        self.current_screen = CurrentScreen::InGame;
+    }
+
+    // Hand p2 to the computer or back to the keyboard, starting a fresh match
+    fn set_opponent(&mut self, single_player: bool) {
+        let controller: Box<dyn Controller> = if single_player {
+            Box::new(Ai::new(Side::Right, self.difficulty, &self.game))
+        } else {
+            Box::new(self.settings.keyboard(Side::Right))
+        };
+        self.set_controller(Side::Right, controller);
+        self.single_player = single_player;
+        self.reset_match();
+    }
+
+    fn set_controller(&mut self, side: Side, controller: Box<dyn Controller>) {
+        self.game.set_movement(side, controller.movement());
+        match side {
+            Side::Left => self.p1_controller = controller,
+            Side::Right => self.p2_controller = controller,
+        }
+    }
+
+    fn controller(&self, side: Side) -> &dyn Controller {
+        match side {
+            Side::Left => self.p1_controller.as_ref(),
+            Side::Right => self.p2_controller.as_ref(),
+        }
+    }
+
+    fn reset_match(&mut self) {
+        self.playback = None;
+        // a network match can't be restarted on one side only
+        if let Some(net) = self.net.take() {
+            if self.recording.is_some() {
+                self.recording = Some(net.log);
+            }
+            self.host = None;
+            self.hand_to_keyboard();
+        }
+        if self.watch.take().is_some() {
+            self.hand_to_keyboard();
+        }
+        let mut config = self.game.config;
+        self.apply_settings(&mut config);
+        self.game.config = config;
+        self.tick_rate = self.settings.tick_rate;
+        // a match nobody has played yet keeps its seed, so `--seed` holds
+        // whatever the menu changes before it starts
+        if self.game.tick == 0 {
+            self.game.restart();
+        } else {
+            self.game.reset();
+        }
+        self.p1_controller.reset(&self.game);
+        self.p2_controller.reset(&self.game);
+        self.previous.clone_from(&self.game);
+        self.inputs = Inputs::default();
+        self.held_keys.clear();
+        if self.recording.is_some() {
+            // keep the match we're throwing away
+            self.save_recording();
+            self.recording = Some(Replay::record(&self.game));
         }
     }
-    fn handle_direction_press_event(&mut self, key_event: KeyEvent) {
+
+    // The entries of the menu on the current screen, top to bottom
+    fn menu_options(&self) -> &'static [CurrentSelection] {
         match self.current_screen {
-            CurrentScreen::InGame => match key_event.code {
-                // Move p1
-                KeyCode::Char('w') => self.p1.direction = if self.p1.direction == Direction::Down {
-                    Direction::Na
-                } else {
-                    Direction::Up
-                },
-                KeyCode::Char('s') => self.p1.direction = if self.p1.direction == Direction::Up {
-                    Direction::Na
-                } else {
-                    Direction::Down
-                },
-                // Move p2
-                KeyCode::Up => self.p2.direction = if self.p2.direction == Direction::Down {
-                    Direction::Na
-                } else {
-                    Direction::Up
-                },
-                KeyCode::Down => self.p2.direction = if self.p2.direction == Direction::Up {
-                    Direction::Na
-                } else {
-                    Direction::Down
-                },
-                _ => {}
-            },
-            CurrentScreen::StartMenu => match self.current_selection {
-                Some(CurrentSelection::NewGame) => {
-                    self.current_selection = Some(CurrentSelection::Exit)
-                }
-                Some(CurrentSelection::Exit) => {
-                    self.current_selection = Some(CurrentSelection::NewGame)
-                }
-                None => (),
-            },
+            CurrentScreen::StartMenu => &[
+                CurrentSelection::OnePlayer,
+                CurrentSelection::NewGame,
+                CurrentSelection::HostGame,
+                CurrentSelection::JoinGame,
+                CurrentSelection::WatchGame,
+                CurrentSelection::Difficulty,
+                CurrentSelection::Settings,
+                CurrentSelection::Exit,
+            ],
+            CurrentScreen::Paused => &[
+                CurrentSelection::Resume,
+                CurrentSelection::Restart,
+                CurrentSelection::Settings,
+                CurrentSelection::QuitToMenu,
+            ],
+            CurrentScreen::Settings => &[
+                CurrentSelection::Setting(Setting::Rules),
+                CurrentSelection::Setting(Setting::Lives),
+                CurrentSelection::Setting(Setting::BallSpeed),
+                CurrentSelection::Setting(Setting::PaddleSize),
+                CurrentSelection::Setting(Setting::TickRate),
+                CurrentSelection::Setting(Setting::Marker),
+                CurrentSelection::Setting(Setting::Theme),
+                CurrentSelection::Setting(Setting::Keys),
+                CurrentSelection::Back,
+            ],
+            CurrentScreen::Keys => &[
+                CurrentSelection::Bind(Action::P1Up),
+                CurrentSelection::Bind(Action::P1Down),
+                CurrentSelection::Bind(Action::P2Up),
+                CurrentSelection::Bind(Action::P2Down),
+                CurrentSelection::Bind(Action::Pause),
+                CurrentSelection::Bind(Action::Confirm),
+                CurrentSelection::Bind(Action::Back),
+                CurrentSelection::Bind(Action::Quit),
+                CurrentSelection::Bind(Action::Debug),
+                CurrentSelection::Bind(Action::Marker),
+                CurrentSelection::ResetKeys,
+                CurrentSelection::Back,
+            ],
+            CurrentScreen::GameOver if self.online() => {
+                &[CurrentSelection::MainMenu, CurrentSelection::Exit]
+            }
+            CurrentScreen::GameOver => &[
+                CurrentSelection::Rematch,
+                CurrentSelection::MainMenu,
+                CurrentSelection::Exit,
+            ],
+            CurrentScreen::InGame | CurrentScreen::Hosting | CurrentScreen::Joining => &[],
+        }
+    }
+
+    fn move_selection(&mut self, direction: Direction) {
+        let options = self.menu_options();
+        let Some(current) = self
+            .current_selection
+            .and_then(|selection| options.iter().position(|&o| o == selection))
+        else {
+            return;
+        };
+        let next = match direction {
+            Direction::Up => current + options.len() - 1,
+            _ => current + 1,
+        };
+        self.current_selection = Some(options[next % options.len()]);
+    }
+    fn handle_direction_press_event(&mut self, direction: Direction) {
+        match self.current_screen {
+            // paddles follow `held_keys` instead, see `on_tick`
+            CurrentScreen::InGame | CurrentScreen::Hosting | CurrentScreen::Joining => {}
+            CurrentScreen::StartMenu
+            | CurrentScreen::Paused
+            | CurrentScreen::Settings
+            | CurrentScreen::Keys
+            | CurrentScreen::GameOver => self.move_selection(direction),
         }
     }
 
@@ -277,42 +1053,41 @@ impl App {
     }
 
     fn render_main_menu(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
         let title = Line::from(" PONG ".bold());
         let instructions = Line::from(vec![
             " Move: ".into(),
-            "<Up>/<Down>".blue().bold(),
+            self.move_hint().fg(theme.keys).bold(),
             " Choose: ".into(),
-            "<Enter>".blue().bold(),
+            self.key_hint(Action::Confirm).fg(theme.keys).bold(),
             " Quit: ".into(),
-            "<Q> ".blue().bold(),
+            format!("{} ", self.key_hint(Action::Quit))
+                .fg(theme.keys)
+                .bold(),
         ]);
         let block = Block::bordered()
             .title(title.centered())
             .title_bottom(instructions.centered())
-            .border_set(border::THICK);
-        let mut lines = vec![];
-
-        lines.push(Line::from(vec![
-            Span::raw(
-                if matches!(self.current_selection, Some(CurrentSelection::NewGame)) {
-                    "◉ "
-                } else {
-                    "  "
-                },
-            ),
-            Span::styled("New Game", Style::default().fg(Color::Yellow)),
-        ]));
-
-        lines.push(Line::from(vec![
-            Span::raw(
-                if matches!(self.current_selection, Some(CurrentSelection::Exit)) {
-                    "◉ "
-                } else {
-                    "  "
-                },
+            .border_set(border::THICK)
+            .border_style(theme.border);
+        let mut lines = vec![
+            self.menu_item(CurrentSelection::OnePlayer, "1 Player"),
+            self.menu_item(CurrentSelection::NewGame, "2 Players"),
+            self.menu_item(CurrentSelection::HostGame, "Host Game"),
+            self.menu_item(CurrentSelection::JoinGame, "Join Game"),
+            self.menu_item(CurrentSelection::WatchGame, "Watch Game"),
+            self.menu_item(
+                CurrentSelection::Difficulty,
+                format!("Difficulty: {}", self.difficulty),
             ),
-            Span::styled("Exit", Style::default().fg(Color::Yellow)),
-        ]));
+            self.menu_item(CurrentSelection::Settings, "Settings"),
+            self.menu_item(CurrentSelection::Exit, "Exit"),
+            Line::from(""),
+            Line::from(self.net_status.clone().unwrap_or_default()).red(),
+        ];
+        if !self.settings.keys.conflicts().is_empty() {
+            lines.push(Line::from("Some keys do two things, see Settings").red());
+        }
 
         let main_menu = Text::from(lines);
 
@@ -323,61 +1098,531 @@ impl App {
             .render(area, buf);
     }
 
-    fn render_game(&self, area: Rect, buf: &mut Buffer) {
-        
-        let mut lifes = String::new();
-        for _i in 0..self.p1.lifes {
-            lifes.push('◉');
+    // How to trigger `action`, for the instructions along the borders
+    fn key_hint(&self, action: Action) -> String {
+        format!("<{}>", key_name(self.settings.keys.key(action)))
+    }
+
+    // Menus move with either player's keys, these are the ones we show
+    fn move_hint(&self) -> String {
+        format!(
+            "{}/{}",
+            self.key_hint(Action::P2Up),
+            self.key_hint(Action::P2Down)
+        )
+    }
+
+    fn render_keys(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let instructions = Line::from(vec![
+            " Move: ".into(),
+            self.move_hint().fg(theme.keys).bold(),
+            " Rebind: ".into(),
+            self.key_hint(Action::Confirm).fg(theme.keys).bold(),
+            " Back: ".into(),
+            format!("{} ", self.key_hint(Action::Back))
+                .fg(theme.keys)
+                .bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from(" KEYS ".bold()).centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK)
+            .border_style(theme.border);
+
+        let keys = &self.settings.keys;
+        let conflicts = keys.conflicts();
+        let mut lines = Vec::new();
+        for &selection in self.menu_options() {
+            lines.push(match selection {
+                CurrentSelection::Bind(action) => {
+                    let clash = conflicts.iter().any(|&(a, b)| a == action || b == action);
+                    let label = format!("{action}: {}", key_name(keys.key(action)));
+                    match (self.rebinding, clash) {
+                        (Some(rebinding), _) if rebinding == action => {
+                            self.menu_item(selection, format!("{action}: ..."))
+                        }
+                        (_, true) => self.menu_item(selection, format!("{label} (!)")),
+                        _ => self.menu_item(selection, label),
+                    }
+                }
+                CurrentSelection::ResetKeys => self.menu_item(selection, "Reset to defaults"),
+                _ => self.menu_item(selection, "Back"),
+            });
         }
-        let p1_lifes = Line::from(vec![lifes.into()]);
-        
-        lifes = String::new();
-        for _i in 0..self.p2.lifes {
-            lifes.push('◉');
+        lines.push(Line::from(""));
+        if let Some(action) = self.rebinding {
+            lines.push(Line::from(format!("Press a key for {action}, <esc> to cancel")).bold());
+        }
+        for (a, b) in conflicts {
+            let key = key_name(keys.key(a));
+            lines.push(Line::from(format!("{key} does both {a} and {b}")).red());
+        }
+        Paragraph::new(Text::from(lines))
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+
+    fn menu_item(&self, selection: CurrentSelection, label: impl Into<String>) -> Line<'static> {
+        let theme = self.theme();
+        let (marker, color) = if self.current_selection == Some(selection) {
+            ("◉ ", theme.highlight)
+        } else {
+            ("  ", theme.menu)
+        };
+        Line::from(vec![
+            Span::raw(marker),
+            Span::styled(label.into(), Style::default().fg(color)),
+        ])
+    }
+
+    // The theme picked in settings, in colors this terminal can show
+    fn theme(&self) -> Theme {
+        self.settings.theme.downgrade(self.color_depth)
+    }
+
+    fn render_game_over(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let title = Line::from(" GAME OVER ".bold());
+        let instructions = Line::from(vec![
+            " Move: ".into(),
+            self.move_hint().fg(theme.keys).bold(),
+            " Choose: ".into(),
+            self.key_hint(Action::Confirm).fg(theme.keys).bold(),
+            " Quit: ".into(),
+            format!("{} ", self.key_hint(Action::Quit))
+                .fg(theme.keys)
+                .bold(),
+        ]);
+        let block = Block::bordered()
+            .title(title.centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK)
+            .border_style(theme.border);
+
+        let game = &self.game;
+        let winner = match game.winner {
+            Some(side @ Side::Left) => format!("{} wins!", self.controller(side).name())
+                .fg(theme.p1)
+                .bold(),
+            Some(side @ Side::Right) => format!("{} wins!", self.controller(side).name())
+                .fg(theme.p2)
+                .bold(),
+            None => "Draw".bold(),
+        };
+        let stats = &game.stats;
+        let seconds = (game.tick as f64 * self.tick_rate.as_secs_f64()) as u64;
+        let mut lines = vec![
+            Line::from(winner),
+            Line::from(""),
+            Line::from(format!(
+                "{}  P1 {} : {} P2",
+                match game.config.rules {
+                    MatchRules::Lives { .. } => "Lifes left",
+                    MatchRules::Points { .. } => "Final score",
+                    MatchRules::Sets { .. } => "Sets",
+                },
+                final_score(game.player(Side::Left), game.config.rules),
+                final_score(game.player(Side::Right), game.config.rules),
+            )),
+            Line::from(format!(
+                "Paddle hits  P1 {} : {} P2",
+                stats.p1_hits, stats.p2_hits
+            )),
+            Line::from(format!("Longest rally: {}", stats.longest_rally)),
+            Line::from(format!("Top speed: {:.2}", stats.top_speed)),
+            Line::from(format!("Match time: {}:{:02}", seconds / 60, seconds % 60)),
+            Line::from(format!("Seed: {}", game.seed)).fg(theme.hud),
+            match &self.playback {
+                Some(playback) => replay_status(playback, &theme),
+                None => Line::from(""),
+            },
+            Line::from(""),
+        ];
+        if !self.online() {
+            lines.push(self.menu_item(CurrentSelection::Rematch, "Rematch"));
         }
-        let p2_lifes = Line::from(vec![lifes.into()]);
+        lines.push(self.menu_item(CurrentSelection::MainMenu, "Main Menu"));
+        lines.push(self.menu_item(CurrentSelection::Exit, "Quit"));
+
+        Paragraph::new(Text::from(lines))
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+
+    fn render_settings(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let instructions = Line::from(vec![
+            " Move: ".into(),
+            self.move_hint().fg(theme.keys).bold(),
+            " Change: ".into(),
+            "<left>/<right>".fg(theme.keys).bold(),
+            " Back: ".into(),
+            format!("{} ", self.key_hint(Action::Back))
+                .fg(theme.keys)
+                .bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from(" SETTINGS ".bold()).centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK)
+            .border_style(theme.border);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        // the rules being played, which `--rules` may have changed
+        let mut shown = self.settings.clone();
+        if let Some(rules) = self.rules_override {
+            shown.rules = rules;
+        }
+        let mut lines = Vec::new();
+        for &selection in self.menu_options() {
+            lines.push(match selection {
+                CurrentSelection::Setting(setting) => {
+                    self.menu_item(selection, format!("{setting}: {}", shown.value(setting)))
+                }
+                _ => self.menu_item(selection, "Back"),
+            });
+        }
+        let note = if self.settings_from == CurrentScreen::Paused {
+            "Changes to the game apply from the next match"
+        } else {
+            ""
+        };
+        lines.push(Line::from(""));
+        lines.push(Line::from(note).fg(theme.hud));
+        let [menu, preview_area] =
+            Layout::vertical([Constraint::Length(lines.len() as u16), Constraint::Min(0)])
+                .areas(inner);
+        Paragraph::new(Text::from(lines))
+            .centered()
+            .render(menu, buf);
+
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let game = &preview.game;
+        let preview_block = Block::bordered()
+            .title(Line::from(" Preview ").centered())
+            .border_style(theme.border);
+        Canvas::default()
+            .block(preview_block)
+            .marker(self.settings.marker())
+            .paint(|ctx| paint_match(ctx, &preview.previous, game, self.alpha, &theme))
+            .x_bounds([0.0, game.width])
+            .y_bounds([0.0, game.height])
+            .render(fit_playground(preview_area, game.width, game.height), buf);
+    }
+
+    // Drawn over the frozen game
+    fn render_pause_menu(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let width = 30.min(area.width);
+        let height = 8.min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        let instructions = Line::from(vec![
+            " Resume: ".into(),
+            format!("{} ", self.key_hint(Action::Back))
+                .fg(theme.keys)
+                .bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from(" PAUSED ".bold()).centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK)
+            .border_style(theme.border);
+        let lines = vec![
+            Line::from(""),
+            self.menu_item(CurrentSelection::Resume, "Resume"),
+            self.menu_item(CurrentSelection::Restart, "Restart"),
+            self.menu_item(CurrentSelection::Settings, "Settings"),
+            self.menu_item(CurrentSelection::QuitToMenu, "Quit to Menu"),
+        ];
+        Clear.render(popup, buf);
+        Paragraph::new(Text::from(lines))
+            .centered()
+            .block(block)
+            .render(popup, buf);
+    }
+
+    fn render_lobby(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let (title, mut lines) = match (&self.current_screen, &self.host) {
+            (CurrentScreen::Hosting, Some(host)) => (
+                " HOST GAME ",
+                vec![
+                    Line::from(format!("Waiting for a player on port {}", host.port())),
+                    Line::from("They pick Join Game and enter this machine's address"),
+                ],
+            ),
+            _ => (
+                if self.watching {
+                    " WATCH GAME "
+                } else {
+                    " JOIN GAME "
+                },
+                vec![
+                    Line::from("Address of the host:"),
+                    Line::from(format!("{}_", self.join_address)).fg(theme.highlight),
+                ],
+            ),
+        };
+        lines.push(Line::from(""));
+        lines.push(Line::from(self.net_status.clone().unwrap_or_default()).red());
+        let instructions = match self.current_screen {
+            CurrentScreen::Joining => Line::from(vec![
+                " Connect: ".into(),
+                "<Enter>".fg(theme.keys).bold(),
+                " Back: ".into(),
+                "<Esc> ".fg(theme.keys).bold(),
+            ]),
+            _ => Line::from(vec![
+                " Back: ".into(),
+                format!("{} ", self.key_hint(Action::Back))
+                    .fg(theme.keys)
+                    .bold(),
+            ]),
+        };
+        let block = Block::bordered()
+            .title(Line::from(title.bold()).centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK)
+            .border_style(theme.border);
+        Paragraph::new(Text::from(lines))
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+
+    fn render_game(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let rules = self.game.config.rules;
+        let p1_score = Line::from(scoreboard(self.game.player(Side::Left), rules));
+        let p2_score = Line::from(scoreboard(self.game.player(Side::Right), rules));
         
         // 1. Create the block that surrounds the game area
-        let instructions = Line::from(vec!["Main Menu:".into(), "<q>".blue().bold()]);
-        let instructions_p1 = Line::from(vec![" Move:".into(), "<w>/<s>".yellow().bold()]);
-        let instructions_p2 = Line::from(vec![" Move:".into(), "<Up>/<Down>".green().bold()]);
+        let quit = self.key_hint(Action::Quit);
+        let instructions = if self.online() {
+            Line::from(vec!["Main Menu:".into(), quit.fg(theme.keys).bold()])
+        } else {
+            Line::from(vec![
+                "Pause:".into(),
+                self.key_hint(Action::Pause).fg(theme.keys).bold(),
+                " Main Menu:".into(),
+                quit.fg(theme.keys).bold(),
+            ])
+        };
+        let instructions_p1 = controller_hint(self.controller(Side::Left), theme.p1);
+        let instructions_p2 = controller_hint(self.controller(Side::Right), theme.p2);
         let block = Block::bordered()
-        .title(Line::from("PONG").centered())
-        .title(p1_lifes.left_aligned())
-        .title(p2_lifes.right_aligned())
+        .title(
+            Line::from(format!("PONG | {} | speed {:.2}", rules, self.game.ball.speed()))
+                .centered(),
+        )
+        .title(p1_score.left_aligned())
+        .title(p2_score.right_aligned())
         .title_bottom(instructions_p1.left_aligned())
         .title_bottom(instructions_p2.right_aligned())
         .title_bottom(instructions.centered())
-        .border_set(border::THICK);
+        .border_set(border::THICK)
+        .border_style(theme.border);
+        let block = match (&self.playback, &self.net) {
+            (Some(playback), _) => block.title(replay_status(playback, &theme).centered()),
+            // lockstep stalls now and then, only say so if it's noticeable
+            (_, Some(net)) if net.stalled > 30 => block.title(
+                Line::from(" Waiting for the other player... ".red().bold()).centered(),
+            ),
+            _ if self.watch.is_some() => {
+                block.title(Line::from(" WATCHING ".fg(theme.hud).bold()).centered())
+            }
+            _ => block,
+        };
     
         // 2. Split the game's renderable area by accounting for the block's margins
         let inner_area = block.inner(area); // The area inside the bordered block
 
         // 3. Define the bounds for the Canvas (x, y ranges correspond to the playground)
-        let x_bounds = [
-            self.playground.left() as f64,
-            self.playground.right() as f64,
-        ];
-        let y_bounds = [
-            self.playground.top() as f64,
-            self.playground.bottom() as f64,
-        ];
+        let x_bounds = [0.0, self.game.width];
+        let y_bounds = [0.0, self.game.height];
 
         // 4. Create the canvas and draw the ball
         let canvas = Canvas::default()
             .block(block) // Attach the block
-            .marker(self.marker)
+            .marker(self.settings.marker())
             .paint(|ctx| {
-                ctx.draw(&self.ball); // Draw the ball at its current position
-                ctx.draw(&self.p1.paddle);
-                ctx.draw(&self.p2.paddle);
+                paint_match(ctx, &self.previous, &self.game, self.alpha, &theme);
 
+                if let Some(ticks) = self.game.serve_countdown() {
+                    let seconds = (ticks as f64 * self.tick_rate.as_secs_f64()).ceil() as u32;
+                    ctx.print(
+                        self.game.width / 2.0,
+                        self.game.height * 0.75,
+                        Line::from(seconds.max(1).to_string()).bold(),
+                    );
+                }
             })
             .x_bounds(x_bounds)
             .y_bounds(y_bounds);
 
         // 5. Render the canvas within the game's area
         canvas.render(inner_area, buf);
+
+        if self.show_debug {
+            self.render_debug(inner_area, buf);
+        }
+    }
+
+    fn render_debug(&self, area: Rect, buf: &mut Buffer) {
+        let theme = self.theme();
+        let ball = &self.game.ball;
+        let mut lines = vec![
+            Line::from(format!("tick: {}", self.game.tick)),
+            Line::from(format!("marker: {:?}", self.settings.marker())),
+            Line::from(format!("ball: ({:.1}, {:.1})", ball.x, ball.y)),
+            Line::from(format!("velocity: ({:.2}, {:.2})", ball.vx, ball.vy)),
+            Line::from(format!("spin: {:+.4}", ball.spin)),
+            Line::from(format!(
+                "paddles: {:+.2} {:+.2}",
+                self.game.p1.velocity, self.game.p2.velocity
+            )),
+        ];
+        if let Some(net) = &self.net {
+            lines.push(Line::from(format!("input delay: {}", net.input_delay)));
+            lines.push(Line::from(format!(
+                "rollbacks: {} (last {} ticks)",
+                net.rollbacks, net.last_rollback
+            )));
+            lines.push(Line::from(format!("spectators: {}", net.spectators())));
+        }
+        Paragraph::new(lines).fg(theme.hud).render(area, buf);
+    }
+}
+
+// Key hint for keyboard players, who is playing otherwise
+fn controller_hint(controller: &dyn Controller, color: Color) -> Line<'static> {
+    match controller.key_hint() {
+        Some(keys) => Line::from(vec![" Move:".into(), keys.fg(color).bold()]),
+        None => Line::from(vec![" ".into(), controller.name().fg(color).bold()]),
+    }
+}
+
+// Whether the replay being watched still matches the recording
+fn replay_status(playback: &Playback, theme: &Theme) -> Line<'static> {
+    match playback.diverged {
+        Some(tick) => Line::from(format!(" REPLAY DIVERGED at tick {tick} ").red().bold()),
+        None => Line::from(" REPLAY ".fg(theme.hud).bold()),
+    }
+}
+
+// What goes in the corner of the game border for this player
+fn scoreboard(player: &Player, rules: MatchRules) -> String {
+    match rules {
+        MatchRules::Lives { .. } => "◉".repeat(player.lifes),
+        MatchRules::Points { .. } => format!(" {} ", player.points),
+        MatchRules::Sets { .. } => format!(" {} (sets {}) ", player.points, player.sets),
+    }
+}
+
+fn final_score(player: &Player, rules: MatchRules) -> u32 {
+    match rules {
+        MatchRules::Lives { .. } => player.lifes as u32,
+        MatchRules::Points { .. } => player.points,
+        MatchRules::Sets { .. } => player.sets,
+    }
+}
+
+// Canvas pixels per terminal cell, horizontally and vertically
+fn marker_resolution(marker: Marker) -> (f64, f64) {
+    match marker {
+        Marker::Braille => (2.0, 4.0),
+        Marker::HalfBlock => (1.0, 2.0),
+        _ => (1.0, 1.0),
+    }
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn lerp_ball(prev: &Ball, next: &Ball, t: f64) -> Ball {
+    Ball {
+        x: lerp(prev.x, next.x, t),
+        y: lerp(prev.y, next.y, t),
+        ..*next
+    }
+}
+
+fn lerp_paddle(prev: &Paddle, next: &Paddle, t: f64) -> Paddle {
+    Paddle {
+        x: lerp(prev.x, next.x, t),
+        y: lerp(prev.y, next.y, t),
+        ..*next
+    }
+}
+
+// The ball and both paddles, `alpha` of the way from `previous` to `game`
+fn paint_match(
+    ctx: &mut canvas::Context,
+    previous: &GameState,
+    game: &GameState,
+    alpha: f64,
+    theme: &Theme,
+) {
+    let ball = lerp_ball(&previous.ball, &game.ball, alpha);
+    ctx.draw(&Circle {
+        x: ball.x,
+        y: ball.y,
+        radius: ball.radius,
+        color: theme.ball,
+    });
+    let p1 = lerp_paddle(&previous.p1.paddle, &game.p1.paddle, alpha);
+    let p2 = lerp_paddle(&previous.p2.paddle, &game.p2.paddle, alpha);
+    ctx.draw(&paddle_shape(&p1, theme.p1));
+    ctx.draw(&paddle_shape(&p2, theme.p2));
+}
+
+// The largest part of `area` that shows a `width` by `height` playground,
+// border included, without stretching it
+fn fit_playground(area: Rect, width: f64, height: f64) -> Rect {
+    let columns = f64::from(area.width.saturating_sub(2));
+    let rows = f64::from(area.height.saturating_sub(2));
+    let aspect = width / (height * CELL_ASPECT);
+    let (columns, rows) = if columns > rows * aspect {
+        (rows * aspect, rows)
+    } else {
+        (columns, columns / aspect)
+    };
+    let fitted_width = (columns as u16 + 2).min(area.width);
+    let fitted_height = (rows as u16 + 2).min(area.height);
+    Rect {
+        x: area.x + (area.width - fitted_width) / 2,
+        y: area.y + (area.height - fitted_height) / 2,
+        width: fitted_width,
+        height: fitted_height,
+    }
+}
+
+fn paddle_shape(paddle: &Paddle, color: Color) -> Rectangle {
+    Rectangle {
+        x: paddle.x,
+        y: paddle.y,
+        width: paddle.width,
+        height: paddle.height,
+        color,
     }
 }
 
@@ -385,7 +1630,15 @@ impl Widget for &App {
     fn render(self, area: Rect, buf: &mut Buffer) {
         match self.current_screen {
             CurrentScreen::InGame => self.render_game(area, buf),
+            CurrentScreen::Paused => {
+                self.render_game(area, buf);
+                self.render_pause_menu(area, buf);
+            }
             CurrentScreen::StartMenu => self.render_main_menu(area, buf),
+            CurrentScreen::Settings => self.render_settings(area, buf),
+            CurrentScreen::Keys => self.render_keys(area, buf),
+            CurrentScreen::GameOver => self.render_game_over(area, buf),
+            CurrentScreen::Hosting | CurrentScreen::Joining => self.render_lobby(area, buf),
         }
     }
 }
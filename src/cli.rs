@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use crate::constants::NET_MAX_ROLLBACK;
+use crate::controller::ControllerSpec;
+use crate::rules::MatchRules;
+
+pub const USAGE: &str = "Usage: pong [OPTIONS]
+
+Options:
+  --rules <RULES>  lives[:N], points[:TO_WIN[:WIN_BY]] or sets[:BEST_OF[:TO_WIN[:WIN_BY]]]
+  --p1 <PLAYER>    Who plays the left paddle: keyboard, ai[:easy|normal|hard]
+                   or script:STEPS, e.g. script:u10,n5,d10
+  --p2 <PLAYER>    Same for the right paddle
+  --seed <SEED>    Start from this seed, to play a match from the game over
+                   screen again
+  --input-delay <TICKS>
+                   Ticks before your key presses take effect in network
+                   matches, 0 to 15, 2 by default. More makes the other
+                   paddle jump around less on a slow connection
+  --record <FILE>  Save the last match played to FILE
+  --replay <FILE>  Play back a match saved with --record
+  --config <FILE>  Read and save settings here instead of
+                   $XDG_CONFIG_HOME/pong/config.toml
+  -h, --help       Print this help";
+
+/// Command line options. Anything left as `None` falls back to the defaults.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub rules: Option<MatchRules>,
+    pub p1: Option<ControllerSpec>,
+    pub p2: Option<ControllerSpec>,
+    pub seed: Option<u64>,
+    pub input_delay: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rules" => {
+                    let value = args.next().ok_or("--rules needs a value")?;
+                    parsed.rules = Some(value.parse()?);
+                }
+                "--p1" => {
+                    let value = args.next().ok_or("--p1 needs a value")?;
+                    parsed.p1 = Some(value.parse()?);
+                }
+                "--p2" => {
+                    let value = args.next().ok_or("--p2 needs a value")?;
+                    parsed.p2 = Some(value.parse()?);
+                }
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a value")?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("'{value}' is not a valid seed"))?;
+                    parsed.seed = Some(seed);
+                }
+                "--input-delay" => {
+                    let value = args.next().ok_or("--input-delay needs a value")?;
+                    let ticks = value
+                        .parse()
+                        .map_err(|_| format!("'{value}' is not a number of ticks"))?;
+                    // no use past the rollback window, and every tick of it is sent up front
+                    if ticks > NET_MAX_ROLLBACK {
+                        return Err(format!(
+                            "--input-delay must be between 0 and {NET_MAX_ROLLBACK} ticks, got {ticks}"
+                        ));
+                    }
+                    parsed.input_delay = Some(ticks);
+                }
+                "--record" => {
+                    let value = args.next().ok_or("--record needs a file")?;
+                    parsed.record = Some(value.into());
+                }
+                "--replay" => {
+                    let value = args.next().ok_or("--replay needs a file")?;
+                    parsed.replay = Some(value.into());
+                }
+                "--config" => {
+                    let value = args.next().ok_or("--config needs a file")?;
+                    parsed.config = Some(value.into());
+                }
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unexpected argument '{arg}'")),
+            }
+        }
+        if parsed.record.is_some() && parsed.replay.is_some() {
+            return Err("--record and --replay can't be used together".to_string());
+        }
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::keymap::{self, Action, Keymap};
+use crate::settings::{self, Settings};
+use crate::theme::Theme;
+
+/// Where the settings are kept unless `--config` says otherwise:
+/// `$XDG_CONFIG_HOME/pong/config.toml`, or `~/.config/pong/config.toml`
+/// when that isn't set.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(dir.join("pong").join("config.toml"))
+}
+
+/// The settings saved at `path`. A missing file is fine, that's just the
+/// defaults.
+pub fn load(path: &Path) -> Result<Settings, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(err) => return Err(format!("could not read {}: {err}", path.display())),
+    };
+    toml::from_str::<ConfigFile>(&text)
+        .map_err(|err| err.to_string().trim_end().to_string())
+        .and_then(ConfigFile::settings)
+        .map_err(|err| format!("{} is not a valid config: {err}", path.display()))
+}
+
+pub fn save(path: &Path, settings: &Settings) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text = toml::to_string(&ConfigFile::from(settings)).map_err(io::Error::other)?;
+    fs::write(path, text)
+}
+
+/// The file as written on disk. Anything left out keeps its default.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    /// Same as `--rules`.
+    rules: String,
+    ball_speed: f64,
+    paddle_size: f64,
+    /// Milliseconds per tick.
+    tick_rate: u64,
+    marker: String,
+    /// Called `colors` before there were themes.
+    #[serde(alias = "colors")]
+    theme: String,
+    /// `[keys]`, one line per action like `p1_up = "w"`. Actions left out
+    /// keep their default key.
+    keys: BTreeMap<String, String>,
+    /// `[themes.<name>]`, themes of the player's own.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    themes: BTreeMap<String, ThemeFile>,
+}
+
+/// One theme from the config file. Colors are names like `light-blue`, 256
+/// color palette indices or `#rrggbb`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    /// The built-in theme that fills in any colors left out, `classic` if
+    /// this is too.
+    base: Option<String>,
+    p1: Option<String>,
+    p2: Option<String>,
+    ball: Option<String>,
+    border: Option<String>,
+    menu: Option<String>,
+    highlight: Option<String>,
+    keys: Option<String>,
+    hud: Option<String>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        ConfigFile::from(&Settings::default())
+    }
+}
+
+impl From<&Settings> for ConfigFile {
+    fn from(settings: &Settings) -> Self {
+        ConfigFile {
+            rules: settings.rules.spec(),
+            ball_speed: settings.ball_speed,
+            paddle_size: settings.paddle_height,
+            tick_rate: settings.tick_rate.as_millis() as u64,
+            marker: settings::marker_name(settings.marker).to_string(),
+            theme: settings.theme.name.clone(),
+            keys: Action::ALL
+                .into_iter()
+                .map(|action| {
+                    let key = keymap::key_name(settings.keys.key(action));
+                    (action.name().to_string(), key)
+                })
+                .collect(),
+            themes: settings
+                .custom_themes
+                .iter()
+                .map(|theme| (theme.name.clone(), ThemeFile::from(theme)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&Theme> for ThemeFile {
+    fn from(theme: &Theme) -> Self {
+        let color = |color| Some(color_name(color));
+        ThemeFile {
+            base: None,
+            p1: color(theme.p1),
+            p2: color(theme.p2),
+            ball: color(theme.ball),
+            border: color(theme.border),
+            menu: color(theme.menu),
+            highlight: color(theme.highlight),
+            keys: color(theme.keys),
+            hud: color(theme.hud),
+        }
+    }
+}
+
+impl ConfigFile {
+    fn settings(self) -> Result<Settings, String> {
+        let (min, max) = settings::BALL_SPEED_RANGE;
+        if !(min..=max).contains(&self.ball_speed) {
+            return Err(format!(
+                "ball_speed must be between {min} and {max}, got {}",
+                self.ball_speed
+            ));
+        }
+        let (min, max) = settings::PADDLE_SIZE_RANGE;
+        if !(min..=max).contains(&self.paddle_size) {
+            return Err(format!(
+                "paddle_size must be between {min} and {max}, got {}",
+                self.paddle_size
+            ));
+        }
+        let (min, max) = settings::TICK_RATE_RANGE;
+        if !(min..=max).contains(&self.tick_rate) {
+            return Err(format!(
+                "tick_rate must be between {min} and {max} milliseconds, got {}",
+                self.tick_rate
+            ));
+        }
+        let mut settings = Settings {
+            custom_themes: parse_themes(self.themes)?,
+            ..Settings::default()
+        };
+        settings.theme = settings
+            .theme_named(&self.theme)
+            .map_err(|err| format!("theme: {err}"))?;
+        Ok(Settings {
+            rules: self.rules.parse().map_err(|err| format!("rules: {err}"))?,
+            ball_speed: self.ball_speed,
+            paddle_height: self.paddle_size,
+            tick_rate: Duration::from_millis(self.tick_rate),
+            marker: settings::parse_marker(&self.marker).map_err(|err| format!("marker: {err}"))?,
+            keys: parse_keys(&self.keys)?,
+            ..settings
+        })
+    }
+}
+
+fn parse_keys(keys: &BTreeMap<String, String>) -> Result<Keymap, String> {
+    let mut keymap = Keymap::default();
+    for (name, key) in keys {
+        let action = Action::ALL
+            .into_iter()
+            .find(|action| action.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Action::ALL.iter().map(|action| action.name()).collect();
+                format!(
+                    "keys: unknown action '{name}', expected one of {}",
+                    names.join(", ")
+                )
+            })?;
+        let key = keymap::parse_key(key).map_err(|err| format!("keys.{name}: {err}"))?;
+        keymap.bind(action, key);
+    }
+    Ok(keymap)
+}
+
+// `light-blue` rather than `LightBlue`, like the rest of the file
+fn color_name(color: Color) -> String {
+    let mut name = String::new();
+    for (i, c) in color.to_string().chars().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() && !name.starts_with('#') {
+            name.push('-');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+fn parse_themes(themes: BTreeMap<String, ThemeFile>) -> Result<Vec<Theme>, String> {
+    let built_in = Theme::built_in();
+    let mut parsed = Vec::new();
+    for (name, file) in themes {
+        if built_in
+            .iter()
+            .any(|theme| theme.name.eq_ignore_ascii_case(&name))
+        {
+            return Err(format!(
+                "themes.{name}: there is a built-in theme called that"
+            ));
+        }
+        let base = file.base.as_deref().unwrap_or("classic");
+        let mut theme = built_in
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(base))
+            .cloned()
+            .ok_or_else(|| format!("themes.{name}.base: no built-in theme called '{base}'"))?;
+        theme.name = name;
+        let fields = [
+            ("p1", file.p1, &mut theme.p1),
+            ("p2", file.p2, &mut theme.p2),
+            ("ball", file.ball, &mut theme.ball),
+            ("border", file.border, &mut theme.border),
+            ("menu", file.menu, &mut theme.menu),
+            ("highlight", file.highlight, &mut theme.highlight),
+            ("keys", file.keys, &mut theme.keys),
+            ("hud", file.hud, &mut theme.hud),
+        ];
+        for (field, value, color) in fields {
+            if let Some(value) = value {
+                *color = value.parse().map_err(|_| {
+                    format!("themes.{}.{field}: unknown color '{value}'", theme.name)
+                })?;
+            }
+        }
+        parsed.push(theme);
+    }
+    Ok(parsed)
+}
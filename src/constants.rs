@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+#[allow(dead_code)]
 pub const PONG: &str = "                                                                                 
 PPPPPPPPPPPPPPPPP        OOOOOOOOO     NNNNNNNN        NNNNNNNN        GGGGGGGGGGGGG
 P::::::::::::::::P     OO:::::::::OO   N:::::::N       N::::::N     GGG::::::::::::G
@@ -15,4 +18,81 @@ PP::::::PP          O:::::::OOO:::::::ON::::::N      N::::::::N  G:::::GGGGGGGG:
 P::::::::P           OO:::::::::::::OO N::::::N       N:::::::N   GG:::::::::::::::G
 P::::::::P             OO:::::::::OO   N::::::N        N::::::N     GGG::::::GGG:::G
 PPPPPPPPPP               OOOOOOOOO     NNNNNNNN         NNNNNNN        GGGGGG   GGGG
-";
\ No newline at end of file
+";
+
+// Simulation runs at a fixed rate regardless of how fast the terminal renders.
+pub const TICK_RATE: Duration = Duration::from_millis(16);
+// Upper bound on ticks simulated per frame so a stalled terminal can't
+// make us fall further and further behind.
+pub const MAX_TICKS_PER_FRAME: u32 = 5;
+
+// Terminals that don't report key releases: a key counts as held for
+// KEY_TAP_HOLD after it is pressed, a couple of ticks, so a tap nudges the
+// paddle. Pressed again within KEY_REPEAT_DELAY it's the OS auto-repeating,
+// and from then on it counts as held for KEY_REPEAT_INTERVAL after every
+// repeat.
+pub const KEY_TAP_HOLD: Duration = Duration::from_millis(40);
+pub const KEY_REPEAT_DELAY: Duration = Duration::from_millis(600);
+pub const KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(120);
+
+// Playground dimensions in canvas units. The height is fixed, the width
+// follows the terminal's aspect ratio and PLAYGROUND_WIDTH is only the
+// starting point until we know how big the terminal is.
+pub const PLAYGROUND_WIDTH: f64 = 200.0;
+pub const PLAYGROUND_HEIGHT: f64 = 100.0;
+pub const MIN_PLAYGROUND_WIDTH: f64 = 80.0;
+// Width of a terminal cell relative to its height, for most fonts
+pub const CELL_ASPECT: f64 = 0.5;
+
+// Paddles
+pub const PADDLE_MARGIN: f64 = 10.0;
+pub const PADDLE_WIDTH: f64 = 3.0;
+pub const PADDLE_HEIGHT: f64 = 20.0;
+// Paddle speeds are in units per tick, acceleration and friction in units
+// per tick per tick
+pub const PADDLE_MAX_SPEED: f64 = 2.0;
+pub const PADDLE_ACCELERATION: f64 = 0.25;
+pub const PADDLE_FRICTION: f64 = 0.35;
+
+// Ball
+pub const BALL_RADIUS: f64 = 5.0;
+// Speeds are in units per tick
+pub const BALL_SPEED: f64 = 1.4;
+// Each paddle hit multiplies the ball speed by this, up to MAX_BALL_SPEED
+pub const BALL_SPEED_UP: f64 = 1.05;
+pub const MAX_BALL_SPEED: f64 = 4.0;
+// Collisions resolved per tick before the rest of the movement is dropped
+pub const MAX_BOUNCES_PER_TICK: usize = 4;
+// Steepest angle (radians from horizontal) a paddle edge can send the ball at
+pub const MAX_BOUNCE_ANGLE: f64 = std::f64::consts::PI / 3.0;
+// Spin (radians of curve per tick) given per unit of paddle velocity on a hit
+pub const SPIN_TRANSFER: f64 = 0.01;
+// Fraction of spin kept from one tick to the next
+pub const SPIN_DECAY: f64 = 0.98;
+// Spin below this is considered gone
+pub const MIN_SPIN: f64 = 1e-4;
+
+// Serve
+// Countdown before the ball is served, about three seconds
+pub const SERVE_TICKS: u32 = 180;
+// Serves leave the centre at up to this angle (radians) from horizontal
+pub const SERVE_ANGLE: f64 = std::f64::consts::PI / 6.0;
+
+pub const STARTING_LIFES: usize = 3;
+
+// Netplay
+pub const NET_PORT: u16 = 4242;
+// Inputs are applied this many ticks after they're sent. More delay means
+// fewer wrong guesses about the other player to roll back, but a paddle that
+// reacts later.
+pub const NET_INPUT_DELAY: u64 = 2;
+// How many ticks we play ahead of the other player's last known input,
+// guessing what they do, before stopping to wait for them
+pub const NET_MAX_ROLLBACK: u64 = 15;
+// Spectators play two ticks at once while more than this many behind
+pub const NET_WATCH_BUFFER: usize = 4;
+// How long to wait for the other side to answer while connecting
+pub const NET_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+// Lines a spectator may fall behind on, about five seconds of play, before
+// the host stops sending them the match
+pub const NET_SPECTATOR_BACKLOG: usize = 300;
@@ -0,0 +1,182 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use crossterm::event::KeyCode;
+
+use crate::ai::{Ai, Difficulty};
+use crate::game::{Direction, GameState, PaddleMovement, Side};
+use crate::input::HeldKeys;
+use crate::keymap::{key_name, Keymap};
+
+/// What a controller gets to look at when deciding a tick.
+pub struct Context<'a> {
+    pub game: &'a GameState,
+    pub side: Side,
+    pub keys: &'a HeldKeys,
+    pub now: Instant,
+}
+
+/// Anything that can drive a paddle: a person at the keyboard, the AI, a
+/// script, ...
+pub trait Controller {
+    /// Where the paddle should go on the next tick.
+    fn intent(&mut self, ctx: &Context) -> Direction;
+
+    /// Shown on the HUD and the game over screen.
+    fn name(&self) -> String;
+
+    /// Keys to show on the HUD, for controllers driven by the keyboard.
+    fn key_hint(&self) -> Option<String> {
+        None
+    }
+
+    /// How this controller's paddle moves.
+    fn movement(&self) -> PaddleMovement {
+        PaddleMovement::default()
+    }
+
+    /// Called when a new match starts.
+    fn reset(&mut self, _game: &GameState) {}
+}
+
+pub struct Keyboard {
+    name: String,
+    up: KeyCode,
+    down: KeyCode,
+}
+
+impl Keyboard {
+    /// A local player on `side`, moving with the paddle keys in `keys`.
+    pub fn new(side: Side, keys: &Keymap) -> Keyboard {
+        let (up, down) = keys.paddle_keys(side);
+        let name = match side {
+            Side::Left => "Player 1",
+            Side::Right => "Player 2",
+        };
+        Keyboard {
+            name: name.to_string(),
+            up,
+            down,
+        }
+    }
+}
+
+impl Controller for Keyboard {
+    fn intent(&mut self, ctx: &Context) -> Direction {
+        ctx.keys.direction(self.up, self.down, ctx.now)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn key_hint(&self) -> Option<String> {
+        Some(format!("<{}>/<{}>", key_name(self.up), key_name(self.down)))
+    }
+}
+
+/// Plays a fixed sequence of moves, looping when it runs out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scripted {
+    steps: Vec<(Direction, u32)>,
+    step: usize,
+    ticks: u32,
+}
+
+impl Scripted {
+    pub fn new(steps: Vec<(Direction, u32)>) -> Scripted {
+        Scripted {
+            steps,
+            step: 0,
+            ticks: 0,
+        }
+    }
+}
+
+impl Controller for Scripted {
+    fn intent(&mut self, _ctx: &Context) -> Direction {
+        let Some(&(direction, count)) = self.steps.get(self.step) else {
+            return Direction::Na;
+        };
+        self.ticks += 1;
+        if self.ticks >= count {
+            self.ticks = 0;
+            self.step = (self.step + 1) % self.steps.len();
+        }
+        direction
+    }
+
+    fn name(&self) -> String {
+        "Script".to_string()
+    }
+
+    fn reset(&mut self, _game: &GameState) {
+        self.step = 0;
+        self.ticks = 0;
+    }
+}
+
+/// Parses comma separated steps like `u10,n5,d10`: up for ten ticks, idle
+/// for five, down for ten.
+impl FromStr for Scripted {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = s
+            .split(',')
+            .map(|step| {
+                let mut chars = step.chars();
+                let direction = match chars.next() {
+                    Some('u') => Direction::Up,
+                    Some('d') => Direction::Down,
+                    Some('n') => Direction::Na,
+                    _ => return Err(format!("script step '{step}' must start with u, d or n")),
+                };
+                let count = chars
+                    .as_str()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("script step '{step}' needs a tick count"))?;
+                Ok((direction, count))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Scripted::new(steps))
+    }
+}
+
+/// A controller picked on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControllerSpec {
+    Keyboard,
+    Ai(Difficulty),
+    Script(Scripted),
+}
+
+impl ControllerSpec {
+    pub fn build(&self, side: Side, game: &GameState, keys: &Keymap) -> Box<dyn Controller> {
+        match self {
+            ControllerSpec::Keyboard => Box::new(Keyboard::new(side, keys)),
+            ControllerSpec::Ai(difficulty) => Box::new(Ai::new(side, *difficulty, game)),
+            ControllerSpec::Script(script) => Box::new(script.clone()),
+        }
+    }
+}
+
+/// Parses `keyboard`, `ai[:easy|normal|hard]` or `script:STEPS`.
+impl FromStr for ControllerSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':').unwrap_or((s, ""));
+        match (kind, value) {
+            ("keyboard", "") => Ok(ControllerSpec::Keyboard),
+            ("ai", "") => Ok(ControllerSpec::Ai(Difficulty::default())),
+            ("ai", difficulty) => Ok(ControllerSpec::Ai(difficulty.parse()?)),
+            ("script", steps) => Ok(ControllerSpec::Script(steps.parse()?)),
+            _ => Err(format!(
+                "unknown controller '{s}', expected keyboard, ai[:DIFFICULTY] or script:STEPS"
+            )),
+        }
+    }
+}
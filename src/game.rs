@@ -0,0 +1,781 @@
+use crate::constants::{
+    BALL_RADIUS, BALL_SPEED, BALL_SPEED_UP, MAX_BALL_SPEED, MAX_BOUNCES_PER_TICK, MAX_BOUNCE_ANGLE,
+    MIN_SPIN, PADDLE_ACCELERATION, PADDLE_FRICTION, PADDLE_HEIGHT, PADDLE_MARGIN, PADDLE_MAX_SPEED,
+    PADDLE_WIDTH, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH, SERVE_ANGLE, SERVE_TICKS, SPIN_DECAY,
+    SPIN_TRANSFER,
+};
+use crate::physics::{self, Contact};
+use crate::rng::Rng;
+use crate::rules::MatchRules;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    #[default]
+    Na,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// What each player wants their paddle to do during one tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Inputs {
+    pub p1: Direction,
+    pub p2: Direction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// Ball waits in the middle until the countdown runs out, then goes
+    /// towards `towards`.
+    Serving {
+        ticks_left: u32,
+        towards: Side,
+    },
+    Playing,
+}
+
+/// Everything that happened during a single `GameState::step`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    /// The ball left the centre towards this side.
+    Serve(Side),
+    PaddleHit(Side),
+    WallBounce,
+    /// The player on this side let the ball through.
+    PointLost(Side),
+    /// The player on this side won a set.
+    SetWon(Side),
+    GameOver {
+        winner: Side,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ball {
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+    pub radius: f64,
+    /// Curve applied to the ball's heading every tick, in radians.
+    /// Positive spin bends the ball upwards.
+    pub spin: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Paddle {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub paddle: Paddle,
+    pub lifes: usize,
+    pub starting_lifes: usize,
+    /// Points in the current game, for point and set based rules.
+    pub points: u32,
+    pub sets: u32,
+    /// What the player asked for on the last tick.
+    pub direction: Direction,
+    /// Vertical paddle speed in units per tick, positive is up.
+    pub velocity: f64,
+    pub movement: PaddleMovement,
+}
+
+/// How a paddle gets up to speed and comes to rest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaddleMovement {
+    /// Speed gained per tick while a direction is held.
+    pub acceleration: f64,
+    /// Speed lost per tick while nothing is held, or while steering
+    /// against the current motion.
+    pub friction: f64,
+    pub max_speed: f64,
+}
+
+impl Default for PaddleMovement {
+    fn default() -> Self {
+        PaddleMovement {
+            acceleration: PADDLE_ACCELERATION,
+            friction: PADDLE_FRICTION,
+            max_speed: PADDLE_MAX_SPEED,
+        }
+    }
+}
+
+impl Ball {
+    pub fn speed(&self) -> f64 {
+        self.vx.hypot(self.vy)
+    }
+
+    /// Rescale the velocity to `speed` while keeping the heading.
+    pub fn set_speed(&mut self, speed: f64) {
+        let current = self.speed();
+        if current > 0.0 {
+            self.vx *= speed / current;
+            self.vy *= speed / current;
+        }
+    }
+}
+
+impl Player {
+    pub fn new(x: f64, y: f64, lifes: usize, movement: PaddleMovement) -> Player {
+        Player {
+            lifes,
+            starting_lifes: lifes,
+            points: 0,
+            sets: 0,
+            direction: Direction::Na,
+            velocity: 0.0,
+            movement,
+            paddle: Paddle {
+                x,
+                y,
+                width: PADDLE_WIDTH,
+                height: PADDLE_HEIGHT,
+            },
+        }
+    }
+
+    /// Steer towards `direction` and move the paddle, staying inside a
+    /// playground of the given height.
+    fn move_paddle(&mut self, direction: Direction, height: f64) {
+        self.direction = direction;
+        let PaddleMovement {
+            acceleration,
+            friction,
+            max_speed,
+        } = self.movement;
+        let target = match direction {
+            Direction::Up => 1.0,
+            Direction::Down => -1.0,
+            Direction::Na => 0.0,
+        };
+        // brake when nothing is held or when turning around
+        if target == 0.0 || target * self.velocity < 0.0 {
+            let braked = self.velocity.abs() - friction;
+            self.velocity = self.velocity.signum() * braked.max(0.0);
+        }
+        self.velocity = (self.velocity + target * acceleration).clamp(-max_speed, max_speed);
+
+        let paddle = &mut self.paddle;
+        let top = height - paddle.height;
+        paddle.y += self.velocity;
+        if paddle.y <= 0.0 || paddle.y >= top {
+            paddle.y = paddle.y.clamp(0.0, top);
+            self.velocity = 0.0;
+        }
+    }
+}
+
+/// Running numbers for the current match, shown when it ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchStats {
+    pub p1_hits: u32,
+    pub p2_hits: u32,
+    /// Paddle hits since the last serve.
+    pub rally: u32,
+    pub longest_rally: u32,
+    pub top_speed: f64,
+}
+
+impl MatchStats {
+    fn record(&mut self, event: GameEvent, ball: &Ball) {
+        match event {
+            GameEvent::Serve(_) => self.rally = 0,
+            GameEvent::PaddleHit(side) => {
+                match side {
+                    Side::Left => self.p1_hits += 1,
+                    Side::Right => self.p2_hits += 1,
+                }
+                self.rally += 1;
+                self.longest_rally = self.longest_rally.max(self.rally);
+            }
+            _ => {}
+        }
+        self.top_speed = self.top_speed.max(ball.speed());
+    }
+}
+
+/// Tunables for a match. Defaults come from `constants`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameConfig {
+    pub rules: MatchRules,
+    /// Playground width, follows the terminal's aspect ratio.
+    pub width: f64,
+    pub paddle_width: f64,
+    pub paddle_height: f64,
+    /// Ball speed on serve, in units per tick.
+    pub ball_speed: f64,
+    /// Multiplier applied to the ball speed on every paddle hit.
+    pub speed_up: f64,
+    /// The ball never gets faster than this.
+    pub max_ball_speed: f64,
+    /// Move both paddles back to the middle before every serve.
+    pub recenter_paddles: bool,
+    pub p1_movement: PaddleMovement,
+    pub p2_movement: PaddleMovement,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            rules: MatchRules::default(),
+            width: PLAYGROUND_WIDTH,
+            paddle_width: PADDLE_WIDTH,
+            paddle_height: PADDLE_HEIGHT,
+            ball_speed: BALL_SPEED,
+            speed_up: BALL_SPEED_UP,
+            max_ball_speed: MAX_BALL_SPEED,
+            recenter_paddles: true,
+            p1_movement: PaddleMovement::default(),
+            p2_movement: PaddleMovement::default(),
+        }
+    }
+}
+
+/// The headless pong simulation. It knows nothing about terminals or
+/// rendering; feed it `Inputs` once per tick and react to the returned events.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameState {
+    pub config: GameConfig,
+    pub width: f64,
+    pub height: f64,
+    pub ball: Ball,
+    pub p1: Player,
+    pub p2: Player,
+    pub tick: u64,
+    pub phase: Phase,
+    pub winner: Option<Side>,
+    pub stats: MatchStats,
+    /// The seed this match started from. Same config, same seed and same
+    /// inputs always play out the same match.
+    pub seed: u64,
+    rng: Rng,
+}
+
+impl GameState {
+    pub fn new(config: GameConfig, seed: u64) -> GameState {
+        let paddle_y = (PLAYGROUND_HEIGHT - config.paddle_height) / 2.0;
+        let mut game = GameState {
+            config,
+            width: config.width,
+            height: PLAYGROUND_HEIGHT,
+            ball: Ball {
+                x: 0.0,
+                y: 0.0,
+                vx: 0.0,
+                vy: 0.0,
+                radius: BALL_RADIUS,
+                spin: 0.0,
+            },
+            p1: Player::new(
+                PADDLE_MARGIN,
+                paddle_y,
+                config.rules.lives(),
+                config.p1_movement,
+            ),
+            p2: Player::new(
+                config.width - PADDLE_MARGIN,
+                paddle_y,
+                config.rules.lives(),
+                config.p2_movement,
+            ),
+            tick: 0,
+            phase: Phase::Playing,
+            winner: None,
+            stats: MatchStats::default(),
+            seed,
+            rng: Rng::new(seed),
+        };
+        game.p1.paddle.width = config.paddle_width;
+        game.p2.paddle.width = config.paddle_width;
+        game.start(seed);
+        game
+    }
+
+    /// Start a new match with full lifes. The seed is drawn from the current
+    /// RNG, so a rematch doesn't replay the exact same serves.
+    pub fn reset(&mut self) {
+        let seed = self.rng.next_u64();
+        self.start(seed);
+    }
+
+    /// Start the same match over, serves and all.
+    pub fn restart(&mut self) {
+        self.start(self.seed);
+    }
+
+    /// A generator for randomness outside the simulation that still has to
+    /// be reproducible, like the AI's aim. Each `stream` gets its own
+    /// sequence, derived from the match seed.
+    pub fn rng_stream(&self, stream: u64) -> Rng {
+        Rng::new(Rng::new(self.seed ^ stream).next_u64())
+    }
+
+    fn start(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+        let height = self.height;
+        let lives = self.config.rules.lives();
+        for player in [&mut self.p1, &mut self.p2] {
+            player.starting_lifes = lives;
+            player.lifes = player.starting_lifes;
+            player.points = 0;
+            player.sets = 0;
+            player.direction = Direction::Na;
+            player.velocity = 0.0;
+            player.paddle.height = self.config.paddle_height;
+            player.paddle.y = (height - player.paddle.height) / 2.0;
+        }
+        self.tick = 0;
+        self.winner = None;
+        self.stats = MatchStats::default();
+        let towards = if self.rng.next_f64() < 0.5 {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        self.prepare_serve(towards);
+    }
+
+    /// Change how one paddle moves, for this match and any rematch.
+    pub fn set_movement(&mut self, side: Side, movement: PaddleMovement) {
+        match side {
+            Side::Left => self.config.p1_movement = movement,
+            Side::Right => self.config.p2_movement = movement,
+        }
+        self.player_mut(side).movement = movement;
+    }
+
+    /// Change the playground width mid-match. The ball keeps its relative
+    /// position and the right paddle stays `PADDLE_MARGIN` from the edge.
+    pub fn resize(&mut self, width: f64, paddle_width: f64) {
+        self.ball.x *= width / self.width;
+        self.width = width;
+        self.config.width = width;
+        self.config.paddle_width = paddle_width;
+        self.p1.paddle.width = paddle_width;
+        self.p2.paddle.width = paddle_width;
+        self.p2.paddle.x = width - PADDLE_MARGIN;
+    }
+
+    /// Hash of everything that affects how the match plays out, used to
+    /// spot replays drifting away from the recorded match.
+    pub fn checksum(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        let ball = &self.ball;
+        for value in [ball.x, ball.y, ball.vx, ball.vy, ball.spin, self.width] {
+            feed(value.to_bits());
+        }
+        for player in [&self.p1, &self.p2] {
+            feed(player.paddle.y.to_bits());
+            feed(player.velocity.to_bits());
+            feed(player.lifes as u64);
+            feed(u64::from(player.points));
+            feed(u64::from(player.sets));
+        }
+        feed(self.tick);
+        feed(self.rng.state());
+        hash
+    }
+
+    /// Ticks until the ball is served, if a serve is pending.
+    pub fn serve_countdown(&self) -> Option<u32> {
+        match self.phase {
+            Phase::Serving { ticks_left, .. } => Some(ticks_left),
+            Phase::Playing => None,
+        }
+    }
+
+    pub fn player(&self, side: Side) -> &Player {
+        match side {
+            Side::Left => &self.p1,
+            Side::Right => &self.p2,
+        }
+    }
+
+    fn player_mut(&mut self, side: Side) -> &mut Player {
+        match side {
+            Side::Left => &mut self.p1,
+            Side::Right => &mut self.p2,
+        }
+    }
+
+    /// Advance the simulation by exactly one tick.
+    pub fn step(&mut self, inputs: Inputs) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.winner.is_some() {
+            return events;
+        }
+        self.tick += 1;
+
+        // Move Paddles
+        self.p1.move_paddle(inputs.p1, self.height);
+        self.p2.move_paddle(inputs.p2, self.height);
+
+        if let Phase::Serving {
+            ticks_left,
+            towards,
+        } = self.phase
+        {
+            if ticks_left > 0 {
+                self.phase = Phase::Serving {
+                    ticks_left: ticks_left - 1,
+                    towards,
+                };
+                return events;
+            }
+            self.serve(towards);
+            events.push(GameEvent::Serve(towards));
+        }
+
+        self.apply_spin();
+        self.move_ball(&mut events);
+
+        // Scoring
+        let ball = self.ball;
+        if ball.x - ball.radius < 0.0 && ball.vx < 0.0 {
+            self.lose_point(Side::Left, &mut events);
+        }
+        if ball.x + ball.radius > self.width && ball.vx > 0.0 {
+            self.lose_point(Side::Right, &mut events);
+        }
+
+        for &event in &events {
+            self.stats.record(event, &self.ball);
+        }
+        events
+    }
+
+    /// Move the ball along its velocity for one tick, bouncing off whatever
+    /// it touches first and continuing with the rest of the movement.
+    fn move_ball(&mut self, events: &mut Vec<GameEvent>) {
+        let mut remaining = 1.0;
+        for _ in 0..MAX_BOUNCES_PER_TICK {
+            let ball = self.ball;
+            let dx = ball.vx * remaining;
+            let dy = ball.vy * remaining;
+
+            let candidates = [
+                (
+                    physics::sweep_walls(&ball, dy, self.height),
+                    GameEvent::WallBounce,
+                ),
+                (
+                    physics::sweep_paddle(&ball, dx, dy, &self.p1.paddle),
+                    GameEvent::PaddleHit(Side::Left),
+                ),
+                (
+                    physics::sweep_paddle(&ball, dx, dy, &self.p2.paddle),
+                    GameEvent::PaddleHit(Side::Right),
+                ),
+            ];
+            let first = candidates
+                .into_iter()
+                .filter_map(|(contact, event)| contact.map(|c| (c, event)))
+                .min_by(|(a, _), (b, _)| a.t.total_cmp(&b.t));
+
+            let Some((contact, event)) = first else {
+                self.ball.x += dx;
+                self.ball.y += dy;
+                return;
+            };
+            self.bounce(contact, dx, dy);
+            match event {
+                GameEvent::PaddleHit(side) => {
+                    // Only the paddle face steers the ball, top/bottom edges
+                    // and corners just reflect it.
+                    if contact.nx != 0.0 && contact.ny == 0.0 {
+                        self.deflect(side);
+                    }
+                    let speed = self.ball.speed() * self.config.speed_up;
+                    self.ball.set_speed(speed.min(self.config.max_ball_speed));
+                    self.ball.spin = self.player(side).velocity * SPIN_TRANSFER;
+                }
+                // the wall flips the vertical direction, so flip the curve too
+                GameEvent::WallBounce => self.ball.spin = -self.ball.spin,
+                _ => {}
+            }
+            events.push(event);
+            remaining *= 1.0 - contact.t;
+        }
+    }
+
+    fn bounce(&mut self, contact: Contact, dx: f64, dy: f64) {
+        let ball = &mut self.ball;
+        ball.x += dx * contact.t;
+        ball.y += dy * contact.t;
+        (ball.vx, ball.vy) = contact.reflect(ball.vx, ball.vy);
+    }
+
+    /// Bend the ball's heading by its spin and let the spin wear off. The
+    /// curve never steepens the ball past `MAX_BOUNCE_ANGLE`.
+    fn apply_spin(&mut self) {
+        let ball = &mut self.ball;
+        if ball.spin == 0.0 {
+            return;
+        }
+        let (sin, cos) = (ball.spin * ball.vx.signum()).sin_cos();
+        let vx = ball.vx * cos - ball.vy * sin;
+        let vy = ball.vx * sin + ball.vy * cos;
+        if (vy / vx).abs() <= MAX_BOUNCE_ANGLE.tan() {
+            ball.vx = vx;
+            ball.vy = vy;
+        }
+        ball.spin *= SPIN_DECAY;
+        if ball.spin.abs() < MIN_SPIN {
+            ball.spin = 0.0;
+        }
+    }
+
+    /// Send the ball back at an angle that depends on where it struck the
+    /// paddle: flat from the centre, up to `MAX_BOUNCE_ANGLE` from the edges.
+    fn deflect(&mut self, side: Side) {
+        let paddle = self.player(side).paddle;
+        let ball = &mut self.ball;
+        let center = paddle.y + paddle.height / 2.0;
+        let reach = paddle.height / 2.0 + ball.radius;
+        let offset = ((ball.y - center) / reach).clamp(-1.0, 1.0);
+        let angle = offset * MAX_BOUNCE_ANGLE;
+        let speed = ball.speed();
+        let heading = match side {
+            Side::Left => 1.0,
+            Side::Right => -1.0,
+        };
+        ball.vx = heading * speed * angle.cos();
+        ball.vy = speed * angle.sin();
+    }
+
+    fn lose_point(&mut self, side: Side, events: &mut Vec<GameEvent>) {
+        events.push(GameEvent::PointLost(side));
+        let scorer = side.opponent();
+        let rules = self.config.rules;
+        let winner = match rules {
+            MatchRules::Lives { .. } => {
+                let player = self.player_mut(side);
+                player.lifes = player.lifes.saturating_sub(1);
+                (player.lifes < 1).then_some(scorer)
+            }
+            MatchRules::Points { .. } => {
+                self.player_mut(scorer).points += 1;
+                self.game_won(scorer).then_some(scorer)
+            }
+            MatchRules::Sets { .. } => {
+                self.player_mut(scorer).points += 1;
+                if self.game_won(scorer) {
+                    self.p1.points = 0;
+                    self.p2.points = 0;
+                    let player = self.player_mut(scorer);
+                    player.sets += 1;
+                    events.push(GameEvent::SetWon(scorer));
+                    rules
+                        .sets_to_win()
+                        .filter(|&needed| player.sets >= needed)
+                        .map(|_| scorer)
+                } else {
+                    None
+                }
+            }
+        };
+
+        if let Some(winner) = winner {
+            self.winner = Some(winner);
+            events.push(GameEvent::GameOver { winner });
+        } else {
+            // the player who just lost the point receives the next serve
+            self.prepare_serve(side);
+        }
+    }
+
+    fn game_won(&self, side: Side) -> bool {
+        let points = self.player(side).points;
+        let other = self.player(side.opponent()).points;
+        self.config.rules.wins_game(points, other)
+    }
+
+    /// Put the ball back in the middle and start the serve countdown.
+    fn prepare_serve(&mut self, towards: Side) {
+        self.ball.x = self.width / 2.0;
+        self.ball.y = self.height / 2.0;
+        self.ball.vx = 0.0;
+        self.ball.vy = 0.0;
+        self.ball.spin = 0.0;
+        if self.config.recenter_paddles {
+            let height = self.height;
+            for player in [&mut self.p1, &mut self.p2] {
+                player.paddle.y = (height - player.paddle.height) / 2.0;
+            }
+        }
+        self.phase = Phase::Serving {
+            ticks_left: SERVE_TICKS,
+            towards,
+        };
+    }
+
+    /// Launch the ball towards `towards` at a random angle.
+    fn serve(&mut self, towards: Side) {
+        let angle = self.rng.range(-SERVE_ANGLE, SERVE_ANGLE);
+        let heading = match towards {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        };
+        let speed = self.config.ball_speed;
+        self.ball.vx = heading * speed * angle.cos();
+        self.ball.vy = speed * angle.sin();
+        self.phase = Phase::Playing;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A rally already under way, with the ball placed by hand
+    fn rally(ball: Ball) -> GameState {
+        let mut game = GameState::new(GameConfig::default(), 1);
+        game.phase = Phase::Playing;
+        game.ball = ball;
+        game
+    }
+
+    fn ball(x: f64, y: f64, vx: f64, vy: f64) -> Ball {
+        Ball {
+            x,
+            y,
+            vx,
+            vy,
+            radius: BALL_RADIUS,
+            spin: 0.0,
+        }
+    }
+
+    fn overlaps(ball: &Ball, paddle: &Paddle) -> bool {
+        let x = ball.x.clamp(paddle.x, paddle.x + paddle.width);
+        let y = ball.y.clamp(paddle.y, paddle.y + paddle.height);
+        (ball.x - x).hypot(ball.y - y) < ball.radius - 1e-9
+    }
+
+    // Play a few ticks and count the paddle hits, making sure the ball never
+    // sinks into the paddle on the way
+    fn paddle_hits(game: &mut GameState) -> usize {
+        let mut hits = 0;
+        for _ in 0..5 {
+            let events = game.step(Inputs::default());
+            hits += events
+                .iter()
+                .filter(|event| matches!(event, GameEvent::PaddleHit(_)))
+                .count();
+            assert!(
+                !overlaps(&game.ball, &game.p1.paddle),
+                "ball {:?} is inside paddle {:?} at tick {}",
+                game.ball,
+                game.p1.paddle,
+                game.tick
+            );
+        }
+        hits
+    }
+
+    #[test]
+    fn fast_ball_bounces_off_the_face_once() {
+        let paddle = GameState::new(GameConfig::default(), 1).p1.paddle;
+        let right = paddle.x + paddle.width;
+        let middle = paddle.y + paddle.height / 2.0;
+        let mut game = rally(ball(
+            right + BALL_RADIUS + 2.0,
+            middle,
+            -MAX_BALL_SPEED,
+            0.0,
+        ));
+        assert_eq!(paddle_hits(&mut game), 1);
+        assert!(game.ball.vx > 0.0);
+    }
+
+    #[test]
+    fn fast_ball_bounces_off_the_top_edge_once() {
+        let paddle = GameState::new(GameConfig::default(), 1).p1.paddle;
+        let top = paddle.y + paddle.height;
+        let vx = 0.5;
+        let vy = -(MAX_BALL_SPEED * MAX_BALL_SPEED - vx * vx).sqrt();
+        let mut game = rally(ball(
+            paddle.x + paddle.width / 2.0,
+            top + BALL_RADIUS + 2.0,
+            vx,
+            vy,
+        ));
+        assert_eq!(paddle_hits(&mut game), 1);
+        assert!(game.ball.vy > 0.0);
+    }
+
+    #[test]
+    fn fast_ball_bounces_off_the_corner_once() {
+        let paddle = GameState::new(GameConfig::default(), 1).p1.paddle;
+        let (corner_x, corner_y) = (paddle.x + paddle.width, paddle.y + paddle.height);
+        // on the diagonal out of the corner, half a tick away from touching
+        let offset = (BALL_RADIUS + MAX_BALL_SPEED / 2.0) / 2f64.sqrt();
+        let speed = MAX_BALL_SPEED / 2f64.sqrt();
+        let mut game = rally(ball(corner_x + offset, corner_y + offset, -speed, -speed));
+        assert_eq!(paddle_hits(&mut game), 1);
+        assert!(game.ball.vx > 0.0 && game.ball.vy > 0.0);
+    }
+
+    #[test]
+    fn best_of_three_sets_ends_after_two_sets_won() {
+        let config = GameConfig {
+            rules: MatchRules::Sets {
+                best_of: 3,
+                to_win: 3,
+                win_by: 2,
+            },
+            ..GameConfig::default()
+        };
+        let mut game = GameState::new(config, 7);
+        // paddles pinned to opposite edges, so plenty of balls get through
+        let inputs = Inputs {
+            p1: Direction::Up,
+            p2: Direction::Down,
+        };
+        let mut sets_won = 0;
+        let mut winner = None;
+        while winner.is_none() && game.tick < 100_000 {
+            for event in game.step(inputs) {
+                match event {
+                    GameEvent::SetWon(_) => sets_won += 1,
+                    GameEvent::GameOver { winner: side } => winner = Some(side),
+                    _ => {}
+                }
+            }
+        }
+        let winner = winner.expect("the match never ended");
+        assert_eq!(game.winner, Some(winner));
+        assert_eq!(game.player(winner).sets, 2);
+        assert!(game.player(winner.opponent()).sets < 2);
+        assert_eq!(sets_won, game.p1.sets + game.p2.sets);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::io::{self, stdout};
+use std::time::Instant;
+
+use crossterm::event::{
+    DisableFocusChange, EnableFocusChange, KeyCode, KeyEvent, KeyEventKind,
+    KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::execute;
+use crossterm::terminal::supports_keyboard_enhancement;
+
+use crate::constants::{KEY_REPEAT_DELAY, KEY_REPEAT_INTERVAL, KEY_TAP_HOLD};
+use crate::game::Direction;
+
+/// Ask the terminal to report key releases. Returns whether it agreed; when
+/// it didn't we fall back to guessing from key repeats.
+pub fn enable_key_release_events() -> bool {
+    if !matches!(supports_keyboard_enhancement(), Ok(true)) {
+        return false;
+    }
+    execute!(
+        stdout(),
+        PushKeyboardEnhancementFlags(
+            KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+        )
+    )
+    .is_ok()
+}
+
+pub fn disable_key_release_events() -> io::Result<()> {
+    execute!(stdout(), PopKeyboardEnhancementFlags)
+}
+
+/// Ask the terminal to tell us when it loses focus, so the game can pause.
+/// Terminals that don't support it just never do.
+pub fn enable_focus_events() -> io::Result<()> {
+    execute!(stdout(), EnableFocusChange)
+}
+
+pub fn disable_focus_events() -> io::Result<()> {
+    execute!(stdout(), DisableFocusChange)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Held {
+    pressed_at: Instant,
+    last_seen: Instant,
+    repeated: bool,
+}
+
+/// Which keys are currently held down.
+///
+/// Terminals that report releases tell us exactly. For the rest a press on
+/// its own is a tap, held for just `KEY_TAP_HOLD`. Once the OS starts
+/// auto-repeating it, the key counts as held until the repeats stop coming
+/// every `KEY_REPEAT_INTERVAL`.
+#[derive(Debug)]
+pub struct HeldKeys {
+    reports_release: bool,
+    keys: HashMap<KeyCode, Held>,
+}
+
+impl HeldKeys {
+    pub fn new(reports_release: bool) -> HeldKeys {
+        HeldKeys {
+            reports_release,
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn handle(&mut self, key_event: KeyEvent, now: Instant) {
+        match key_event.kind {
+            KeyEventKind::Press if !self.repeats(key_event.code, now) => {
+                self.keys.insert(
+                    key_event.code,
+                    Held {
+                        pressed_at: now,
+                        last_seen: now,
+                        repeated: false,
+                    },
+                );
+            }
+            // without release events, repeats arrive as more presses
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                if let Some(held) = self.keys.get_mut(&key_event.code) {
+                    held.last_seen = now;
+                    held.repeated = true;
+                }
+            }
+            KeyEventKind::Release => {
+                self.keys.remove(&key_event.code);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    fn held(&self, code: KeyCode, now: Instant) -> Option<Held> {
+        let held = *self.keys.get(&code)?;
+        if self.reports_release {
+            return Some(held);
+        }
+        let timeout = if held.repeated {
+            KEY_REPEAT_INTERVAL
+        } else {
+            KEY_TAP_HOLD
+        };
+        (now.duration_since(held.last_seen) <= timeout).then_some(held)
+    }
+
+    // Whether a press of `code` is the OS repeating a key still held down,
+    // rather than a new press. The first repeat can take a while, long after
+    // a tap would have been let go.
+    fn repeats(&self, code: KeyCode, now: Instant) -> bool {
+        let Some(held) = self.keys.get(&code) else {
+            return false;
+        };
+        let timeout = if held.repeated {
+            KEY_REPEAT_INTERVAL
+        } else {
+            KEY_REPEAT_DELAY
+        };
+        self.reports_release || now.duration_since(held.last_seen) <= timeout
+    }
+
+    /// Paddle direction for a pair of keys. If both are down the one pressed
+    /// last wins.
+    pub fn direction(&self, up: KeyCode, down: KeyCode, now: Instant) -> Direction {
+        match (self.held(up, now), self.held(down, now)) {
+            (Some(u), Some(d)) if u.pressed_at >= d.pressed_at => Direction::Up,
+            (Some(_), Some(_)) => Direction::Down,
+            (Some(_), None) => Direction::Up,
+            (None, Some(_)) => Direction::Down,
+            (None, None) => Direction::Na,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crossterm::event::KeyModifiers;
+
+    use super::*;
+
+    fn press(keys: &mut HeldKeys, code: KeyCode, at: Instant) {
+        keys.handle(KeyEvent::new(code, KeyModifiers::NONE), at);
+    }
+
+    fn direction(keys: &HeldKeys, at: Instant) -> Direction {
+        keys.direction(KeyCode::Up, KeyCode::Down, at)
+    }
+
+    #[test]
+    fn tap_only_nudges_the_paddle() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut keys = HeldKeys::new(false);
+        press(&mut keys, KeyCode::Up, start);
+        assert_eq!(direction(&keys, ms(16)), Direction::Up);
+        assert_eq!(direction(&keys, ms(100)), Direction::Na);
+        // pressed again much later is another tap
+        press(&mut keys, KeyCode::Up, ms(1000));
+        assert_eq!(direction(&keys, ms(1016)), Direction::Up);
+        assert_eq!(direction(&keys, ms(1100)), Direction::Na);
+    }
+
+    #[test]
+    fn key_is_held_while_it_repeats() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut keys = HeldKeys::new(false);
+        press(&mut keys, KeyCode::Down, start);
+        // the OS waits a while before repeating, then repeats every 30ms
+        for at in (500..=800).step_by(30) {
+            press(&mut keys, KeyCode::Down, ms(at));
+        }
+        assert_eq!(direction(&keys, ms(850)), Direction::Down);
+        assert_eq!(keys.keys[&KeyCode::Down].pressed_at, start);
+        assert_eq!(direction(&keys, ms(1000)), Direction::Na);
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+
+use crossterm::event::KeyCode;
+
+use crate::game::Side;
+
+/// Something a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    P1Up,
+    P1Down,
+    P2Up,
+    P2Down,
+    /// Pause and resume a match.
+    Pause,
+    /// Pick the selected menu entry.
+    Confirm,
+    /// Close whatever is open: pause, settings, the lobby.
+    Back,
+    /// Leave the match for the start menu, or quit from there.
+    Quit,
+    Debug,
+    /// Switch to the next way of drawing the playground.
+    Marker,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::P1Up,
+        Action::P1Down,
+        Action::P2Up,
+        Action::P2Down,
+        Action::Pause,
+        Action::Confirm,
+        Action::Back,
+        Action::Quit,
+        Action::Debug,
+        Action::Marker,
+    ];
+
+    /// How the action is written in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::P1Up => "p1_up",
+            Action::P1Down => "p1_down",
+            Action::P2Up => "p2_up",
+            Action::P2Down => "p2_down",
+            Action::Pause => "pause",
+            Action::Confirm => "confirm",
+            Action::Back => "back",
+            Action::Quit => "quit",
+            Action::Debug => "debug",
+            Action::Marker => "marker",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::P1Up => "P1 up",
+            Action::P1Down => "P1 down",
+            Action::P2Up => "P2 up",
+            Action::P2Down => "P2 down",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+            Action::Quit => "Quit",
+            Action::Debug => "Debug info",
+            Action::Marker => "Next marker",
+        })
+    }
+}
+
+/// Which key does what. Every action has exactly one key, but nothing stops
+/// two actions from sharing one, see `conflicts`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keymap {
+    keys: [KeyCode; Action::ALL.len()],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            keys: [
+                KeyCode::Char('w'),
+                KeyCode::Char('s'),
+                KeyCode::Up,
+                KeyCode::Down,
+                KeyCode::Char('p'),
+                KeyCode::Enter,
+                KeyCode::Esc,
+                KeyCode::Char('q'),
+                KeyCode::Char('d'),
+                KeyCode::Char('m'),
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    pub fn key(&self, action: Action) -> KeyCode {
+        self.keys[action as usize]
+    }
+
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        self.keys[action as usize] = key;
+    }
+
+    /// Everything `key` is bound to, usually one action or none.
+    pub fn actions(&self, key: KeyCode) -> impl Iterator<Item = Action> + '_ {
+        Action::ALL
+            .into_iter()
+            .filter(move |&action| self.key(action) == key)
+    }
+
+    /// Up and down for the paddle on `side`.
+    pub fn paddle_keys(&self, side: Side) -> (KeyCode, KeyCode) {
+        match side {
+            Side::Left => (self.key(Action::P1Up), self.key(Action::P1Down)),
+            Side::Right => (self.key(Action::P2Up), self.key(Action::P2Down)),
+        }
+    }
+
+    /// Pairs of actions bound to the same key.
+    pub fn conflicts(&self) -> Vec<(Action, Action)> {
+        let mut conflicts = Vec::new();
+        for (i, &a) in Action::ALL.iter().enumerate() {
+            for &b in &Action::ALL[i + 1..] {
+                if self.key(a) == self.key(b) {
+                    conflicts.push((a, b));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+// Keys without a printable character of their own, by the name they go by
+// in the config file
+const NAMED_KEYS: [(KeyCode, &str); 22] = [
+    (KeyCode::Char(' '), "space"),
+    (KeyCode::Up, "up"),
+    (KeyCode::Down, "down"),
+    (KeyCode::Left, "left"),
+    (KeyCode::Right, "right"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Esc, "esc"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::BackTab, "backtab"),
+    (KeyCode::Backspace, "backspace"),
+    (KeyCode::Delete, "delete"),
+    (KeyCode::Insert, "insert"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+    (KeyCode::CapsLock, "capslock"),
+    (KeyCode::ScrollLock, "scrolllock"),
+    (KeyCode::NumLock, "numlock"),
+    (KeyCode::PrintScreen, "printscreen"),
+    (KeyCode::Pause, "pause"),
+    (KeyCode::Menu, "menu"),
+];
+
+/// How a key is written in the config file and shown on screen: the
+/// character itself for printable keys, a lowercase name otherwise.
+pub fn key_name(key: KeyCode) -> String {
+    if let Some((_, name)) = NAMED_KEYS.iter().find(|&&(named, _)| named == key) {
+        return name.to_string();
+    }
+    match key {
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{n}"),
+        other => other.to_string().to_lowercase(),
+    }
+}
+
+/// Whether `key` survives being saved, media keys for one don't.
+pub fn can_bind(key: KeyCode) -> bool {
+    parse_key(&key_name(key)) == Ok(key)
+}
+
+/// The key `key_name` calls `s`.
+pub fn parse_key(s: &str) -> Result<KeyCode, String> {
+    let mut chars = s.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+    if let Some(&(key, _)) = NAMED_KEYS.iter().find(|&&(_, name)| name == s) {
+        return Ok(key);
+    }
+    match s.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        Some(n) => Ok(KeyCode::F(n)),
+        None => Err(format!("unknown key '{s}'")),
+    }
+}
@@ -1,14 +1,74 @@
+mod ai;
 mod app;
+mod cli;
+mod config;
 mod constants;
+mod controller;
+mod game;
+mod input;
+mod keymap;
+mod net;
+mod physics;
+mod replay;
+mod rng;
+mod rules;
+mod settings;
+mod theme;
 use crate::app::App;
+use crate::cli::Args;
+use crate::replay::Replay;
 
 use std::io;
 
-use ratatui;
-
 fn main() -> io::Result<()> {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) if args.help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // fail before taking over the terminal
+    let replay = match args.replay.as_deref().map(Replay::load).transpose() {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(2);
+        }
+    };
+
+    let config_path = args.config.clone().or_else(config::default_path);
+    let settings = match config_path.as_deref().map(config::load).transpose() {
+        Ok(settings) => settings.unwrap_or_default(),
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(2);
+        }
+    };
+
     let mut terminal = ratatui::init();
-    let app_result = App::new().run(&mut terminal);
+    let reports_key_release = input::enable_key_release_events();
+    let focus_events = input::enable_focus_events();
+    let mut app = App::new(args, settings, config_path, reports_key_release);
+    if let Some(replay) = replay {
+        app.play_replay(replay);
+    }
+    let app_result = app.run(&mut terminal);
+    // the flags live on the alternate screen, pop them before leaving it
+    let popped = if reports_key_release {
+        input::disable_key_release_events()
+    } else {
+        Ok(())
+    };
+    let unfocused = match focus_events {
+        Ok(()) => input::disable_focus_events(),
+        Err(_) => Ok(()),
+    };
     ratatui::restore();
-    app_result
+    app_result.and(popped).and(unfocused)
 }
@@ -0,0 +1,754 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError};
+use std::thread;
+
+use crate::constants::{
+    NET_HANDSHAKE_TIMEOUT, NET_MAX_ROLLBACK, NET_SPECTATOR_BACKLOG, NET_WATCH_BUFFER,
+};
+use crate::controller::{Context, Controller};
+use crate::game::{Direction, GameState, Inputs, Side};
+use crate::replay::{direction_char, parse_direction, Replay, CHECKSUM_INTERVAL};
+
+/// Bump whenever the messages change.
+pub const PROTOCOL_VERSION: u32 = 5;
+
+// Both sides have to run the exact same simulation, so the game version has
+// to match as well as the protocol.
+fn hello() -> String {
+    format!("pong-net {PROTOCOL_VERSION} {}", env!("CARGO_PKG_VERSION"))
+}
+
+// The protocol is line based text over TCP:
+//
+//   joiner -> host   pong-net PROTOCOL VERSION play|watch
+//   host -> joiner   `reject REASON`, or
+//   host -> player   the header of a replay file (seed and config), ending
+//                    with `---`
+//   both ways        i TICK INPUT   the sender's input for TICK, for every
+//                                   tick from 0 on
+//   both ways        c TICK HASH    checksum of the confirmed game after
+//                                   TICK, every `CHECKSUM_INTERVAL` ticks
+//   host -> watcher  the whole match so far as a replay file, ending with
+//                    `===`, then
+//                    t TICK P1 P2   both inputs for TICK, once confirmed
+//   all ways         bye            leaving the match
+enum Message {
+    Input { tick: u64, direction: Direction },
+    Confirmed { tick: u64, inputs: Inputs },
+    Checksum { tick: u64, hash: u64 },
+    Bye,
+    Lost(String),
+}
+
+fn parse_message(line: &str) -> Message {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields.as_slice() {
+        ["i", tick, direction] => match (tick.parse(), parse_direction(direction)) {
+            (Ok(tick), Ok(direction)) => Message::Input { tick, direction },
+            _ => Message::Lost(format!("bad input '{line}'")),
+        },
+        ["t", tick, p1, p2] => match (tick.parse(), parse_direction(p1), parse_direction(p2)) {
+            (Ok(tick), Ok(p1), Ok(p2)) => Message::Confirmed {
+                tick,
+                inputs: Inputs { p1, p2 },
+            },
+            _ => Message::Lost(format!("bad inputs '{line}'")),
+        },
+        ["c", tick, hash] => match (tick.parse(), u64::from_str_radix(hash, 16)) {
+            (Ok(tick), Ok(hash)) => Message::Checksum { tick, hash },
+            _ => Message::Lost(format!("bad checksum '{line}'")),
+        },
+        ["bye"] => Message::Bye,
+        _ => Message::Lost(format!("unexpected message '{line}'")),
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    messages: Receiver<Message>,
+}
+
+impl Connection {
+    // Takes over once the handshake is done. Anything the handshake read
+    // past is still in `reader`.
+    fn new(reader: BufReader<TcpStream>) -> io::Result<Connection> {
+        let stream = reader.get_ref().try_clone()?;
+        stream.set_read_timeout(None)?;
+        stream.set_nodelay(true)?;
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || read_messages(reader, sender));
+        Ok(Connection { stream, messages })
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        self.stream.write_all(format!("{line}\n").as_bytes())
+    }
+}
+
+// Say goodbye however we leave, and wake up the reader thread so it ends
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.send("bye");
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+// Runs on its own thread, forwarding messages until the connection ends
+fn read_messages(reader: BufReader<TcpStream>, sender: Sender<Message>) {
+    for line in reader.lines() {
+        let message = match line {
+            Ok(line) => parse_message(&line),
+            Err(err) => Message::Lost(format!("connection lost: {err}")),
+        };
+        let last = !matches!(
+            message,
+            Message::Input { .. } | Message::Confirmed { .. } | Message::Checksum { .. }
+        );
+        if sender.send(message).is_err() || last {
+            return;
+        }
+    }
+    let _ = sender.send(Message::Lost("connection closed".to_string()));
+}
+
+/// What `Netplay::exchange` came back with.
+pub enum Exchange {
+    /// Step the game with these, the other player's half may be a guess.
+    Ready(Inputs),
+    /// Too far ahead of the other player to keep guessing, or the match is
+    /// over here but they haven't confirmed it yet.
+    Waiting,
+    /// Both players agree the match is over, the game holds the final state.
+    Finished,
+    Closed(String),
+}
+
+/// A match against a player on another instance.
+///
+/// Our inputs are sent `input_delay` ticks ahead of when they apply. The
+/// other player's inputs usually arrive late, so we guess they kept doing
+/// what they last did and play on. Next to that predicted game we keep the
+/// confirmed one, only stepped with inputs we've actually received. When a
+/// guess turns out wrong the predicted game rolls back to the confirmed one
+/// and is simulated forward again with the real inputs.
+pub struct Netplay {
+    /// The side played on this instance.
+    pub side: Side,
+    pub input_delay: u64,
+    /// Ticks in a row spent waiting for the other player.
+    pub stalled: u32,
+    /// How often a wrong guess was rolled back, and how many ticks the last
+    /// one had to simulate again.
+    pub rollbacks: u32,
+    pub last_rollback: u64,
+    /// The confirmed match so far, ready to be saved.
+    pub log: Replay,
+    connection: Connection,
+    // people watching, only ever on the host, see `feed_spectator`
+    spectators: Vec<SyncSender<String>>,
+    confirmed: GameState,
+    // inputs from the confirmed tick on, the front one is for `confirmed.tick`
+    local: VecDeque<Direction>,
+    remote: VecDeque<Direction>,
+    // what we guessed the other player did on the ticks only predicted so far
+    guesses: VecDeque<Direction>,
+    // their last input we know for sure, which is the next guess
+    last_remote: Direction,
+    // checksums of the confirmed game, ours and theirs, until both sides'
+    // for the same tick are in
+    checksums: VecDeque<(u64, u64)>,
+    remote_checksums: VecDeque<(u64, u64)>,
+    // tick the next input sent or received applies to
+    next_local: u64,
+    next_remote: u64,
+}
+
+impl Netplay {
+    fn new(
+        side: Side,
+        mut connection: Connection,
+        game: &GameState,
+        input_delay: u64,
+    ) -> io::Result<Netplay> {
+        // nobody has pressed anything during the first ticks
+        for tick in 0..input_delay {
+            connection.send(&format!("i {tick} n"))?;
+        }
+        Ok(Netplay {
+            side,
+            input_delay,
+            stalled: 0,
+            rollbacks: 0,
+            last_rollback: 0,
+            log: Replay::record(game),
+            connection,
+            spectators: Vec::new(),
+            confirmed: game.clone(),
+            local: VecDeque::from(vec![Direction::Na; input_delay as usize]),
+            remote: VecDeque::new(),
+            guesses: VecDeque::new(),
+            last_remote: Direction::Na,
+            checksums: VecDeque::new(),
+            remote_checksums: VecDeque::new(),
+            next_local: input_delay,
+            next_remote: 0,
+        })
+    }
+
+    /// Whether `exchange` wants our input for the tick `game` is on.
+    pub fn needs_input(&self, tick: u64) -> bool {
+        self.next_local == tick + self.input_delay
+    }
+
+    /// Send our input, if `needs_input` asked for it, take in whatever the
+    /// other player sent, roll `game` back if we guessed wrong, and work out
+    /// the inputs for its next step.
+    pub fn exchange(&mut self, game: &mut GameState, local: Option<Direction>) -> Exchange {
+        if let Some(direction) = local {
+            let line = format!("i {} {}", self.next_local, direction_char(direction));
+            if let Err(err) = self.connection.send(&line) {
+                return Exchange::Closed(format!("connection lost: {err}"));
+            }
+            self.local.push_back(direction);
+            self.next_local += 1;
+        }
+
+        loop {
+            match self.connection.messages.try_recv() {
+                Ok(Message::Input { tick, direction }) if tick == self.next_remote => {
+                    self.remote.push_back(direction);
+                    self.next_remote += 1;
+                }
+                Ok(Message::Input { tick, .. }) => {
+                    return Exchange::Closed(format!(
+                        "out of sync, got the input for tick {tick} instead of {}",
+                        self.next_remote
+                    ))
+                }
+                Ok(Message::Checksum { tick, hash }) => {
+                    self.remote_checksums.push_back((tick, hash))
+                }
+                Ok(Message::Confirmed { .. }) => {
+                    return Exchange::Closed("the other player isn't playing".to_string())
+                }
+                Ok(Message::Bye) => return Exchange::Closed("The other player left".to_string()),
+                Ok(Message::Lost(reason)) => return Exchange::Closed(reason),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Exchange::Closed("connection closed".to_string())
+                }
+            }
+        }
+
+        // catch the confirmed game up with the predicted one where we can
+        let mut guessed_wrong = false;
+        while self.confirmed.tick < game.tick && !self.remote.is_empty() {
+            let remote = self.remote.pop_front().unwrap_or_default();
+            let local = self.local.pop_front().unwrap_or_default();
+            let inputs = self.inputs(local, remote);
+            let line = format!(
+                "t {} {} {}\n",
+                self.confirmed.tick,
+                direction_char(inputs.p1),
+                direction_char(inputs.p2)
+            );
+            // whoever can't keep up stops watching
+            self.spectators
+                .retain(|spectator| spectator.try_send(line.clone()).is_ok());
+            self.log.record_inputs(inputs);
+            self.confirmed.step(inputs);
+            self.log.record_state(&self.confirmed);
+            if self.confirmed.tick.is_multiple_of(CHECKSUM_INTERVAL) {
+                let checksum = (self.confirmed.tick, self.confirmed.checksum());
+                let line = format!("c {} {:016x}", checksum.0, checksum.1);
+                if let Err(err) = self.connection.send(&line) {
+                    return Exchange::Closed(format!("connection lost: {err}"));
+                }
+                self.checksums.push_back(checksum);
+            }
+            guessed_wrong |= self.guesses.pop_front() != Some(remote);
+            self.last_remote = remote;
+        }
+        // the same inputs only play out the same where floating point math
+        // does, which isn't a given between different machines
+        while let (Some(&ours), Some(&theirs)) =
+            (self.checksums.front(), self.remote_checksums.front())
+        {
+            if ours != theirs {
+                return Exchange::Closed(format!(
+                    "out of sync, the games differ at tick {}",
+                    ours.0
+                ));
+            }
+            self.checksums.pop_front();
+            self.remote_checksums.pop_front();
+        }
+        if self.confirmed.winner.is_some() {
+            game.clone_from(&self.confirmed);
+            return Exchange::Finished;
+        }
+        if guessed_wrong {
+            self.rollback(game);
+        }
+        if game.winner.is_some() {
+            return Exchange::Waiting;
+        }
+
+        let ahead = game.tick - self.confirmed.tick;
+        if ahead >= NET_MAX_ROLLBACK {
+            self.stalled += 1;
+            return Exchange::Waiting;
+        }
+        self.stalled = 0;
+        let ahead = ahead as usize;
+        let remote = self.remote.get(ahead).copied().unwrap_or(self.last_remote);
+        self.guesses.push_back(remote);
+        Exchange::Ready(self.inputs(self.local[ahead], remote))
+    }
+
+    /// Let `guest` watch, starting with the match so far.
+    pub fn add_spectator(&mut self, guest: Guest) -> io::Result<()> {
+        let stream = guest.reader.into_inner();
+        stream.set_nodelay(true)?;
+        // a spectator who stops reading only holds up their own thread, and
+        // not even that for long
+        stream.set_write_timeout(Some(NET_HANDSHAKE_TIMEOUT))?;
+        let (lines, backlog) = mpsc::sync_channel(NET_SPECTATOR_BACKLOG);
+        let _ = lines.try_send(format!("{}===\n", self.log));
+        thread::spawn(move || feed_spectator(stream, backlog));
+        self.spectators.push(lines);
+        Ok(())
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    // Replace the predicted game with the confirmed one and predict the
+    // same ticks again, now from what the other player really did
+    fn rollback(&mut self, game: &mut GameState) {
+        let ticks = game.tick - self.confirmed.tick;
+        game.clone_from(&self.confirmed);
+        self.guesses.clear();
+        for ahead in 0..ticks as usize {
+            let remote = self.remote.get(ahead).copied().unwrap_or(self.last_remote);
+            self.guesses.push_back(remote);
+            game.step(self.inputs(self.local[ahead], remote));
+        }
+        self.rollbacks += 1;
+        self.last_rollback = ticks;
+    }
+
+    fn inputs(&self, local: Direction, remote: Direction) -> Inputs {
+        match self.side {
+            Side::Left => Inputs {
+                p1: local,
+                p2: remote,
+            },
+            Side::Right => Inputs {
+                p1: remote,
+                p2: local,
+            },
+        }
+    }
+}
+
+// Runs on its own thread, sending a spectator the match as it's confirmed
+// until the host hangs up on them or they stop reading
+fn feed_spectator(mut stream: TcpStream, lines: Receiver<String>) {
+    for line in lines {
+        if stream.write_all(line.as_bytes()).is_err() {
+            return;
+        }
+    }
+    let _ = stream.write_all(b"bye\n");
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Waits for someone to join a match. The host plays the left paddle.
+pub struct Host {
+    listener: TcpListener,
+    input_delay: u64,
+    // each handshake runs on a thread of its own, so nobody slow to say
+    // hello holds up the match, and ends up here
+    guests: Receiver<io::Result<Guest>>,
+    handshakes: Sender<io::Result<Guest>>,
+}
+
+impl Host {
+    pub fn listen(port: u16, input_delay: u64) -> io::Result<Host> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let (handshakes, guests) = mpsc::channel();
+        Ok(Host {
+            listener,
+            input_delay,
+            guests,
+            handshakes,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |addr| addr.port())
+    }
+
+    /// Check for someone connecting, without blocking. What they get to do
+    /// is up to the caller, see `Guest`.
+    pub fn accept(&self) -> io::Result<Option<Guest>> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let handshakes = self.handshakes.clone();
+                    let input_delay = self.input_delay;
+                    thread::spawn(move || {
+                        let _ = handshakes.send(handshake(stream, input_delay));
+                    });
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        match self.guests.try_recv() {
+            Ok(guest) => guest.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+// Find out what someone who just connected wants. Blocks for up to
+// `NET_HANDSHAKE_TIMEOUT`.
+fn handshake(stream: TcpStream, input_delay: u64) -> io::Result<Guest> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(NET_HANDSHAKE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let theirs = line.trim_end();
+    let watching = match theirs.strip_prefix(&hello()) {
+        Some(" play") => false,
+        Some(" watch") => true,
+        _ => {
+            let reason = format!(
+                "version mismatch, they run '{theirs}', we run '{}'",
+                hello()
+            );
+            let _ = writeln!(reader.get_ref(), "reject {reason}");
+            return Err(io::Error::other(format!(
+                "Someone could not join: {reason}"
+            )));
+        }
+    };
+    Ok(Guest {
+        reader,
+        watching,
+        input_delay,
+    })
+}
+
+/// Someone who connected to a host, either to play or to watch.
+pub struct Guest {
+    reader: BufReader<TcpStream>,
+    pub watching: bool,
+    input_delay: u64,
+}
+
+impl Guest {
+    /// Play the match `game` is set up for against them. Returns it freshly
+    /// started.
+    pub fn play(self, game: &GameState) -> io::Result<(Netplay, GameState)> {
+        let replay = Replay::record(game);
+        self.reader
+            .get_ref()
+            .write_all(replay.to_string().as_bytes())?;
+        let game = replay.start();
+        let netplay = Netplay::new(
+            Side::Left,
+            Connection::new(self.reader)?,
+            &game,
+            self.input_delay,
+        )?;
+        Ok((netplay, game))
+    }
+
+    pub fn reject(self, reason: &str) {
+        let _ = writeln!(self.reader.get_ref(), "reject {reason}");
+    }
+}
+
+/// Join the match hosted at `address`, playing the right paddle. Blocks
+/// until the host answers or `NET_HANDSHAKE_TIMEOUT` runs out.
+pub fn join(address: &str, input_delay: u64) -> io::Result<(Netplay, GameState)> {
+    let mut reader = connect(address, "play")?;
+    let header = read_until(&mut reader, "---")?;
+    let replay: Replay = header.parse().map_err(io::Error::other)?;
+    let game = replay.start();
+    let netplay = Netplay::new(Side::Right, Connection::new(reader)?, &game, input_delay)?;
+    Ok((netplay, game))
+}
+
+/// Watch the match hosted at `address`. Blocks like `join`, and returns the
+/// game caught up with the match so far.
+pub fn watch(address: &str) -> io::Result<(Watch, GameState)> {
+    let mut reader = connect(address, "watch")?;
+    let replay: Replay = read_until(&mut reader, "===")?
+        .parse()
+        .map_err(io::Error::other)?;
+    let mut game = replay.start();
+    for &inputs in &replay.inputs {
+        game.step(inputs);
+        if let Some(tick) = replay.check(&game) {
+            return Err(io::Error::other(format!(
+                "out of sync with the host at tick {tick}"
+            )));
+        }
+    }
+    let watch = Watch {
+        connection: Connection::new(reader)?,
+        pending: VecDeque::new(),
+        next_tick: game.tick,
+        ended: None,
+    };
+    Ok((watch, game))
+}
+
+fn connect(address: &str, role: &str) -> io::Result<BufReader<TcpStream>> {
+    let addr = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("{address} doesn't resolve to anything")))?;
+    let mut stream = TcpStream::connect_timeout(&addr, NET_HANDSHAKE_TIMEOUT)?;
+    stream.set_read_timeout(Some(NET_HANDSHAKE_TIMEOUT))?;
+    writeln!(stream, "{} {role}", hello())?;
+    Ok(BufReader::new(stream))
+}
+
+// Everything the host sends up to and including the `end` line
+fn read_until(reader: &mut BufReader<TcpStream>, end: &str) -> io::Result<String> {
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::other("the host hung up"));
+        }
+        if let Some(reason) = line.strip_prefix("reject ") {
+            return Err(io::Error::other(format!(
+                "The host turned us down: {}",
+                reason.trim_end()
+            )));
+        }
+        if line.trim_end() == end {
+            return Ok(text);
+        }
+        text.push_str(&line);
+    }
+}
+
+/// Follows a match hosted elsewhere, without playing in it.
+pub struct Watch {
+    connection: Connection,
+    pending: VecDeque<Inputs>,
+    next_tick: u64,
+    // why the host stopped sending, told once `pending` has played out
+    ended: Option<String>,
+}
+
+impl Watch {
+    /// Inputs to step the game with this tick. Usually one set, two when
+    /// we've fallen behind, none when the host is ahead of us.
+    pub fn take(&mut self) -> Result<Vec<Inputs>, String> {
+        loop {
+            match self.connection.messages.try_recv() {
+                Ok(Message::Confirmed { tick, inputs }) if tick == self.next_tick => {
+                    self.pending.push_back(inputs);
+                    self.next_tick += 1;
+                }
+                Ok(Message::Confirmed { tick, .. }) => {
+                    return Err(format!(
+                        "out of sync, got tick {tick} instead of {}",
+                        self.next_tick
+                    ))
+                }
+                Ok(Message::Input { .. } | Message::Checksum { .. }) => {
+                    return Err("the host isn't hosting".to_string())
+                }
+                Ok(Message::Bye) => {
+                    self.ended = Some("The match is over, the host left".to_string())
+                }
+                Ok(Message::Lost(reason)) => self.ended = Some(reason),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.ended
+                        .get_or_insert_with(|| "connection closed".to_string());
+                    break;
+                }
+            }
+        }
+        if self.pending.is_empty() {
+            if let Some(reason) = &self.ended {
+                return Err(reason.clone());
+            }
+        }
+        let count = if self.pending.len() > NET_WATCH_BUFFER {
+            2
+        } else {
+            1
+        };
+        let count = count.min(self.pending.len());
+        Ok(self.pending.drain(..count).collect())
+    }
+}
+
+/// Stands in for a player on another instance. Their inputs arrive through
+/// `Netplay` or `Watch` and get stepped into the game, so asked directly this
+/// makes the same guess `Netplay` does: they keep doing what they last did.
+pub struct Remote {
+    name: String,
+}
+
+impl Remote {
+    pub fn new(name: impl Into<String>) -> Remote {
+        Remote { name: name.into() }
+    }
+}
+
+impl Controller for Remote {
+    fn intent(&mut self, ctx: &Context) -> Direction {
+        ctx.game.player(ctx.side).direction
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::constants::NET_INPUT_DELAY;
+    use crate::game::GameConfig;
+    use crate::rules::MatchRules;
+
+    // Both players wander up and down out of step, so guesses go wrong and
+    // get rolled back
+    fn direction(tick: u64, period: u64) -> Direction {
+        match tick / period % 3 {
+            0 => Direction::Up,
+            1 => Direction::Down,
+            _ => Direction::Na,
+        }
+    }
+
+    // Keeps calling `step` until it's true, failing the test if that takes
+    // unreasonably long
+    fn wait_for(what: &str, mut step: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while !step() {
+            assert!(Instant::now() < deadline, "timed out waiting for {what}");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // One tick of the match for one of the players, true once it's over
+    fn play(netplay: &mut Netplay, game: &mut GameState, period: u64) -> bool {
+        let local = netplay
+            .needs_input(game.tick)
+            .then(|| direction(game.tick, period));
+        match netplay.exchange(game, local) {
+            Exchange::Ready(inputs) => {
+                game.step(inputs);
+                false
+            }
+            Exchange::Waiting => false,
+            Exchange::Finished => true,
+            Exchange::Closed(reason) => panic!("netplay closed: {reason}"),
+        }
+    }
+
+    #[test]
+    fn host_rejects_another_version() {
+        let host = Host::listen(0, NET_INPUT_DELAY).unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", host.port())).unwrap();
+        writeln!(stream, "pong-net 0 0.0.0 play").unwrap();
+        let mut error = None;
+        wait_for("the handshake", || match host.accept() {
+            Ok(guest) => {
+                assert!(guest.is_none(), "a guest with the wrong version got in");
+                false
+            }
+            Err(err) => {
+                error = Some(err);
+                true
+            }
+        });
+        assert!(error.unwrap().to_string().contains("version mismatch"));
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        assert!(line.starts_with("reject version mismatch"), "got '{line}'");
+    }
+
+    #[test]
+    fn players_and_spectator_finish_with_the_same_game() {
+        let delay = NET_INPUT_DELAY;
+        let host = Host::listen(0, delay).unwrap();
+        let address = format!("127.0.0.1:{}", host.port());
+        let config = GameConfig {
+            rules: MatchRules::Lives { lives: 2 },
+            ..GameConfig::default()
+        };
+        let setup = GameState::new(config, 99);
+
+        let joining = thread::spawn({
+            let address = address.clone();
+            move || join(&address, delay).unwrap()
+        });
+        let mut guest = None;
+        wait_for("the guest", || {
+            guest = host.accept().unwrap();
+            guest.is_some()
+        });
+        let guest = guest.unwrap();
+        assert!(!guest.watching);
+        let (mut host_net, mut host_game) = guest.play(&setup).unwrap();
+        let (mut guest_net, mut guest_game) = joining.join().unwrap();
+        assert_eq!(host_game, guest_game);
+
+        let mut watching = None;
+        let (mut host_done, mut guest_done) = (false, false);
+        wait_for("the end of the match", || {
+            if !host_done {
+                host_done = play(&mut host_net, &mut host_game, 17);
+            }
+            if !guest_done {
+                guest_done = play(&mut guest_net, &mut guest_game, 23);
+            }
+            // someone tunes in halfway through
+            if host_game.tick >= 100 && watching.is_none() {
+                let address = address.clone();
+                watching = Some(thread::spawn(move || watch(&address).unwrap()));
+            }
+            if let Some(spectator) = host.accept().unwrap() {
+                assert!(spectator.watching);
+                host_net.add_spectator(spectator).unwrap();
+            }
+            host_done && guest_done && host_net.spectators() == 1
+        });
+        assert!(host_game.winner.is_some());
+        assert_eq!(host_game, guest_game);
+        assert_eq!(host_net.spectators(), 1);
+
+        let (mut watch, mut watched) = watching.unwrap().join().unwrap();
+        assert!(watched.tick < host_game.tick);
+        drop(host_net);
+        wait_for("the spectator", || {
+            for inputs in watch.take().unwrap() {
+                watched.step(inputs);
+            }
+            watched.winner.is_some()
+        });
+        assert_eq!(watched, host_game);
+    }
+}
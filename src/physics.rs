@@ -0,0 +1,131 @@
+use crate::game::{Ball, Paddle};
+
+/// Where along a sweep the ball first touches something, and the surface
+/// normal at that point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// Fraction of the sweep travelled before impact, in 0.0..=1.0
+    pub t: f64,
+    pub nx: f64,
+    pub ny: f64,
+}
+
+impl Contact {
+    /// Mirror a velocity around the contact normal.
+    pub fn reflect(&self, vx: f64, vy: f64) -> (f64, f64) {
+        let dot = vx * self.nx + vy * self.ny;
+        (vx - 2.0 * dot * self.nx, vy - 2.0 * dot * self.ny)
+    }
+
+    fn approaching(&self, dx: f64, dy: f64) -> bool {
+        dx * self.nx + dy * self.ny < 0.0
+    }
+}
+
+/// Sweep the ball vertically by `dy` against the floor (y = 0) and ceiling (y = height).
+pub fn sweep_walls(ball: &Ball, dy: f64, height: f64) -> Option<Contact> {
+    let (target, ny) = if dy < 0.0 {
+        (ball.radius, 1.0)
+    } else if dy > 0.0 {
+        (height - ball.radius, -1.0)
+    } else {
+        return None;
+    };
+    let t = ((target - ball.y) / dy).max(0.0);
+    (t <= 1.0).then_some(Contact { t, nx: 0.0, ny })
+}
+
+/// Swept circle-vs-rectangle test.
+///
+/// The paddle is inflated by the ball radius (a rounded rectangle) and the
+/// ball centre is cast as a ray against it. Only contacts where the ball is
+/// moving into the surface count, so a ball that was just reflected can't
+/// hit the same face again on the next sweep.
+pub fn sweep_paddle(ball: &Ball, dx: f64, dy: f64, paddle: &Paddle) -> Option<Contact> {
+    let r = ball.radius;
+    let (left, right) = (paddle.x, paddle.x + paddle.width);
+    let (bottom, top) = (paddle.y, paddle.y + paddle.height);
+
+    let (tx_enter, tx_exit) = slab(ball.x, dx, left - r, right + r)?;
+    let (ty_enter, ty_exit) = slab(ball.y, dy, bottom - r, top + r)?;
+    let t_enter = tx_enter.max(ty_enter);
+    let t_exit = tx_exit.min(ty_exit);
+    if t_enter > t_exit || t_exit < 0.0 || t_enter > 1.0 {
+        return None;
+    }
+
+    // Already overlapping, e.g. the paddle moved onto the ball this tick.
+    // Push it out of whichever face it is closest to.
+    if t_enter < 0.0 {
+        let nx = if ball.x < (left + right) / 2.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let contact = Contact {
+            t: 0.0,
+            nx,
+            ny: 0.0,
+        };
+        return contact.approaching(dx, dy).then_some(contact);
+    }
+
+    let hx = ball.x + dx * t_enter;
+    let hy = ball.y + dy * t_enter;
+    let in_x = (left..=right).contains(&hx);
+    let in_y = (bottom..=top).contains(&hy);
+
+    let contact = if in_x || in_y {
+        // Flat face: the normal is along whichever axis we entered last.
+        if tx_enter >= ty_enter {
+            Contact {
+                t: t_enter,
+                nx: -dx.signum(),
+                ny: 0.0,
+            }
+        } else {
+            Contact {
+                t: t_enter,
+                nx: 0.0,
+                ny: -dy.signum(),
+            }
+        }
+    } else {
+        // Rounded corner.
+        let cx = if hx < left { left } else { right };
+        let cy = if hy < bottom { bottom } else { top };
+        let t = ray_circle(ball.x - cx, ball.y - cy, dx, dy, r)?;
+        if !(0.0..=1.0).contains(&t) {
+            return None;
+        }
+        let nx = (ball.x + dx * t - cx) / r;
+        let ny = (ball.y + dy * t - cy) / r;
+        Contact { t, nx, ny }
+    };
+    contact.approaching(dx, dy).then_some(contact)
+}
+
+/// Entry and exit times of a 1D ray through the interval `min..max`.
+fn slab(origin: f64, delta: f64, min: f64, max: f64) -> Option<(f64, f64)> {
+    if delta == 0.0 {
+        return (min..=max)
+            .contains(&origin)
+            .then_some((f64::NEG_INFINITY, f64::INFINITY));
+    }
+    let t1 = (min - origin) / delta;
+    let t2 = (max - origin) / delta;
+    Some((t1.min(t2), t1.max(t2)))
+}
+
+/// First time a ray starting at (ox, oy) relative to a circle centre hits
+/// the circle of radius `r`.
+fn ray_circle(ox: f64, oy: f64, dx: f64, dy: f64, r: f64) -> Option<f64> {
+    let a = dx * dx + dy * dy;
+    let b = 2.0 * (ox * dx + oy * dy);
+    let c = ox * ox + oy * oy - r * r;
+    let disc = b * b - 4.0 * a * c;
+    if a == 0.0 || disc < 0.0 {
+        return None;
+    }
+    Some((-b - disc.sqrt()) / (2.0 * a))
+}
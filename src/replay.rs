@@ -0,0 +1,371 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::controller::{Context, Controller};
+use crate::game::{Direction, GameConfig, GameState, Inputs, PaddleMovement, Side};
+
+/// Bump when the file format or the simulation changes in a way that makes
+/// old replays play out differently.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// A checksum of the game state is stored this often, in ticks. Netplay
+/// compares them just as often.
+pub const CHECKSUM_INTERVAL: u64 = 60;
+
+/// Everything needed to play a match again: how it was set up and what both
+/// players did on every tick.
+///
+/// The file is plain text. A header with the seed and config, then one line
+/// per event: `i COUNT P1 P2` for a run of identical inputs (`u`, `d` or `n`),
+/// `r TICK WIDTH PADDLE_WIDTH` for a playground resize and `c TICK HASH` for
+/// a state checksum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub config: GameConfig,
+    pub inputs: Vec<Inputs>,
+    /// Resizes applied before the step at `tick`.
+    pub resizes: Vec<Resize>,
+    pub checksums: Vec<(u64, u64)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resize {
+    pub tick: u64,
+    pub width: f64,
+    pub paddle_width: f64,
+}
+
+impl Replay {
+    /// Start recording the match `game` is about to play.
+    pub fn record(game: &GameState) -> Replay {
+        Replay {
+            seed: game.seed,
+            config: game.config,
+            inputs: Vec::new(),
+            resizes: Vec::new(),
+            checksums: Vec::new(),
+        }
+    }
+
+    /// Inputs about to be fed to `GameState::step`.
+    pub fn record_inputs(&mut self, inputs: Inputs) {
+        self.inputs.push(inputs);
+    }
+
+    /// Call after every step.
+    pub fn record_state(&mut self, game: &GameState) {
+        if game.tick.is_multiple_of(CHECKSUM_INTERVAL) || game.winner.is_some() {
+            self.checksums.push((game.tick, game.checksum()));
+        }
+    }
+
+    pub fn record_resize(&mut self, game: &GameState) {
+        self.resizes.push(Resize {
+            tick: game.tick,
+            width: game.config.width,
+            paddle_width: game.config.paddle_width,
+        });
+    }
+
+    /// A fresh game in the state the recorded match started from.
+    pub fn start(&self) -> GameState {
+        GameState::new(self.config, self.seed)
+    }
+
+    /// Apply whatever the recording did to `game` before its next step.
+    pub fn apply_resizes(&self, game: &mut GameState) {
+        let tick = game.tick;
+        for resize in self.resizes.iter().filter(|r| r.tick == tick) {
+            game.resize(resize.width, resize.paddle_width);
+        }
+    }
+
+    /// The tick at which `game` no longer matches the recording, if it
+    /// doesn't. Call after every step.
+    pub fn check(&self, game: &GameState) -> Option<u64> {
+        self.checksums
+            .iter()
+            .find(|(tick, _)| *tick == game.tick)
+            .filter(|(_, hash)| *hash != game.checksum())
+            .map(|(tick, _)| *tick)
+    }
+
+    pub fn controller(&self, side: Side) -> ReplayController {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|inputs| match side {
+                Side::Left => inputs.p1,
+                Side::Right => inputs.p2,
+            })
+            .collect();
+        ReplayController { inputs, tick: 0 }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+        text.parse()
+            .map_err(|err| format!("{} is not a valid replay: {err}", path.display()))
+    }
+}
+
+/// How inputs are written in replays and sent over the network.
+pub fn direction_char(direction: Direction) -> char {
+    match direction {
+        Direction::Up => 'u',
+        Direction::Down => 'd',
+        Direction::Na => 'n',
+    }
+}
+
+pub fn parse_direction(s: &str) -> Result<Direction, String> {
+    match s {
+        "u" => Ok(Direction::Up),
+        "d" => Ok(Direction::Down),
+        "n" => Ok(Direction::Na),
+        _ => Err(format!("'{s}' is not an input, expected u, d or n")),
+    }
+}
+
+fn movement_line(name: &str, movement: &PaddleMovement) -> String {
+    format!(
+        "{name} {} {} {}\n",
+        movement.acceleration, movement.friction, movement.max_speed
+    )
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let config = &self.config;
+        writeln!(f, "pong-replay {REPLAY_VERSION}")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "rules {}", config.rules.spec())?;
+        writeln!(f, "width {}", config.width)?;
+        writeln!(f, "paddle_width {}", config.paddle_width)?;
+        writeln!(f, "paddle_height {}", config.paddle_height)?;
+        writeln!(f, "ball_speed {}", config.ball_speed)?;
+        writeln!(f, "speed_up {}", config.speed_up)?;
+        writeln!(f, "max_ball_speed {}", config.max_ball_speed)?;
+        writeln!(f, "recenter_paddles {}", config.recenter_paddles)?;
+        f.write_str(&movement_line("p1_movement", &config.p1_movement))?;
+        f.write_str(&movement_line("p2_movement", &config.p2_movement))?;
+        writeln!(f, "---")?;
+
+        // interleave everything in tick order
+        let mut resizes = self.resizes.iter().peekable();
+        let mut checksums = self.checksums.iter().peekable();
+        let mut tick = 0;
+        let mut inputs = self.inputs.iter().peekable();
+        loop {
+            while let Some(resize) = resizes.next_if(|r| r.tick <= tick) {
+                writeln!(
+                    f,
+                    "r {} {} {}",
+                    resize.tick, resize.width, resize.paddle_width
+                )?;
+            }
+            let Some(&current) = inputs.next() else {
+                break;
+            };
+            let mut count = 1;
+            // a run ends early if something else happens in the middle of it
+            let next_event = resizes
+                .peek()
+                .map(|r| r.tick)
+                .into_iter()
+                .chain(checksums.peek().map(|c| c.0))
+                .min()
+                .unwrap_or(u64::MAX);
+            while tick + count < next_event && inputs.next_if_eq(&&current).is_some() {
+                count += 1;
+            }
+            writeln!(
+                f,
+                "i {count} {} {}",
+                direction_char(current.p1),
+                direction_char(current.p2)
+            )?;
+            tick += count;
+            while let Some((at, hash)) = checksums.next_if(|c| c.0 <= tick) {
+                writeln!(f, "c {at} {hash:016x}")?;
+            }
+        }
+        for (at, hash) in checksums {
+            writeln!(f, "c {at} {hash:016x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate();
+        let mut replay = Replay {
+            seed: 0,
+            config: GameConfig::default(),
+            inputs: Vec::new(),
+            resizes: Vec::new(),
+            checksums: Vec::new(),
+        };
+
+        match lines.next().map(|(_, line)| line.split_once(' ')) {
+            Some(Some(("pong-replay", version))) if version == REPLAY_VERSION.to_string() => {}
+            Some(Some(("pong-replay", version))) => {
+                return Err(format!(
+                    "replay version {version} is not supported, this build plays version {REPLAY_VERSION}"
+                ))
+            }
+            _ => return Err("missing 'pong-replay' header".to_string()),
+        }
+
+        for (number, line) in lines.by_ref() {
+            let at = |err: String| format!("line {}: {err}", number + 1);
+            if line == "---" {
+                break;
+            }
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| at(format!("expected 'key value', got '{line}'")))?;
+            let config = &mut replay.config;
+            match key {
+                "seed" => replay.seed = parse(value).map_err(at)?,
+                "rules" => config.rules = value.parse().map_err(at)?,
+                "width" => config.width = parse(value).map_err(at)?,
+                "paddle_width" => config.paddle_width = parse(value).map_err(at)?,
+                "paddle_height" => config.paddle_height = parse(value).map_err(at)?,
+                "ball_speed" => config.ball_speed = parse(value).map_err(at)?,
+                "speed_up" => config.speed_up = parse(value).map_err(at)?,
+                "max_ball_speed" => config.max_ball_speed = parse(value).map_err(at)?,
+                "recenter_paddles" => config.recenter_paddles = parse(value).map_err(at)?,
+                "p1_movement" => config.p1_movement = parse_movement(value).map_err(at)?,
+                "p2_movement" => config.p2_movement = parse_movement(value).map_err(at)?,
+                _ => return Err(at(format!("unknown setting '{key}'"))),
+            }
+        }
+
+        for (number, line) in lines {
+            let at = |err: String| format!("line {}: {err}", number + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["i", count, p1, p2] => {
+                    let count: usize = parse(count).map_err(at)?;
+                    let inputs = Inputs {
+                        p1: parse_direction(p1).map_err(at)?,
+                        p2: parse_direction(p2).map_err(at)?,
+                    };
+                    replay.inputs.extend(std::iter::repeat_n(inputs, count));
+                }
+                ["r", tick, width, paddle_width] => replay.resizes.push(Resize {
+                    tick: parse(tick).map_err(at)?,
+                    width: parse(width).map_err(at)?,
+                    paddle_width: parse(paddle_width).map_err(at)?,
+                }),
+                ["c", tick, hash] => replay.checksums.push((
+                    parse(tick).map_err(at)?,
+                    u64::from_str_radix(hash, 16)
+                        .map_err(|_| at(format!("'{hash}' is not a checksum")))?,
+                )),
+                [] => {}
+                _ => return Err(at(format!("unexpected line '{line}'"))),
+            }
+        }
+        Ok(replay)
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("'{value}' is not a valid value"))
+}
+
+fn parse_movement(value: &str) -> Result<PaddleMovement, String> {
+    let numbers = value
+        .split_whitespace()
+        .map(parse)
+        .collect::<Result<Vec<f64>, _>>()?;
+    match numbers.as_slice() {
+        &[acceleration, friction, max_speed] => Ok(PaddleMovement {
+            acceleration,
+            friction,
+            max_speed,
+        }),
+        _ => Err(format!(
+            "expected acceleration, friction and max speed, got '{value}'"
+        )),
+    }
+}
+
+/// Plays back one side of a recorded match.
+pub struct ReplayController {
+    inputs: Vec<Direction>,
+    tick: usize,
+}
+
+impl Controller for ReplayController {
+    fn intent(&mut self, _ctx: &Context) -> Direction {
+        let direction = self.inputs.get(self.tick).copied().unwrap_or_default();
+        self.tick += 1;
+        direction
+    }
+
+    fn name(&self) -> String {
+        "Replay".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Some made up play: both paddles wander up and down out of step
+    fn inputs(tick: u64) -> Inputs {
+        let direction = |period: u64| match tick / period % 3 {
+            0 => Direction::Up,
+            1 => Direction::Down,
+            _ => Direction::Na,
+        };
+        Inputs {
+            p1: direction(17),
+            p2: direction(23),
+        }
+    }
+
+    #[test]
+    fn replay_survives_text_and_plays_back_the_same() {
+        let mut game = GameState::new(GameConfig::default(), 1234);
+        let mut replay = Replay::record(&game);
+        while game.winner.is_none() && game.tick < 5000 {
+            if game.tick == 300 {
+                game.resize(game.width * 1.5, game.config.paddle_width);
+                replay.record_resize(&game);
+            }
+            let inputs = inputs(game.tick);
+            replay.record_inputs(inputs);
+            game.step(inputs);
+            replay.record_state(&game);
+        }
+        assert!(!replay.checksums.is_empty());
+
+        let parsed: Replay = replay.to_string().parse().unwrap();
+        assert_eq!(parsed, replay);
+
+        let mut playback = parsed.start();
+        for &inputs in &parsed.inputs {
+            parsed.apply_resizes(&mut playback);
+            playback.step(inputs);
+            assert_eq!(parsed.check(&playback), None);
+        }
+        assert_eq!(playback.checksum(), game.checksum());
+        assert_eq!(playback, game);
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::STARTING_LIFES;
+
+/// How a match is won.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchRules {
+    /// Every missed ball costs a life, last player standing wins.
+    Lives { lives: usize },
+    /// First to `to_win` points, with a lead of at least `win_by`.
+    Points { to_win: u32, win_by: u32 },
+    /// Sets are played like `Points`, the first to win a majority of
+    /// `best_of` sets takes the match.
+    Sets {
+        best_of: u32,
+        to_win: u32,
+        win_by: u32,
+    },
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules::Lives {
+            lives: STARTING_LIFES,
+        }
+    }
+}
+
+impl MatchRules {
+    /// The rule sets offered on the settings screen.
+    pub const PRESETS: [MatchRules; 4] = [
+        MatchRules::Lives {
+            lives: STARTING_LIFES,
+        },
+        MatchRules::Points {
+            to_win: 11,
+            win_by: 2,
+        },
+        MatchRules::Sets {
+            best_of: 3,
+            to_win: 11,
+            win_by: 2,
+        },
+        MatchRules::Sets {
+            best_of: 5,
+            to_win: 11,
+            win_by: 2,
+        },
+    ];
+
+    /// The `--rules` argument that gives these rules back, see `FromStr`.
+    pub fn spec(&self) -> String {
+        match *self {
+            MatchRules::Lives { lives } => format!("lives:{lives}"),
+            MatchRules::Points { to_win, win_by } => format!("points:{to_win}:{win_by}"),
+            MatchRules::Sets {
+                best_of,
+                to_win,
+                win_by,
+            } => format!("sets:{best_of}:{to_win}:{win_by}"),
+        }
+    }
+
+    /// Lifes each player starts with, zero when the rules don't use lifes.
+    pub fn lives(&self) -> usize {
+        match *self {
+            MatchRules::Lives { lives } => lives,
+            _ => 0,
+        }
+    }
+
+    /// Whether `points` against `other` wins a game under these rules.
+    /// Always false for `Lives`, which is decided by lifes running out.
+    pub fn wins_game(&self, points: u32, other: u32) -> bool {
+        match *self {
+            MatchRules::Lives { .. } => false,
+            MatchRules::Points { to_win, win_by } | MatchRules::Sets { to_win, win_by, .. } => {
+                points >= to_win && points >= other + win_by
+            }
+        }
+    }
+
+    /// Sets needed to take the match, if the match is played in sets.
+    pub fn sets_to_win(&self) -> Option<u32> {
+        match *self {
+            MatchRules::Sets { best_of, .. } => Some(best_of / 2 + 1),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MatchRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MatchRules::Lives { lives } => write!(f, "{lives} lifes"),
+            MatchRules::Points { to_win, win_by: 1 } => write!(f, "First to {to_win}"),
+            MatchRules::Points { to_win, win_by } => {
+                write!(f, "First to {to_win}, win by {win_by}")
+            }
+            MatchRules::Sets {
+                best_of, to_win, ..
+            } => write!(f, "Best of {best_of} sets to {to_win}"),
+        }
+    }
+}
+
+/// Parses `lives[:N]`, `points[:TO_WIN[:WIN_BY]]` and
+/// `sets[:BEST_OF[:TO_WIN[:WIN_BY]]]`, e.g. `points:21` or `sets:5`.
+impl FromStr for MatchRules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let numbers = parts
+            .map(|part| {
+                part.parse::<u32>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("'{part}' is not a positive number"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let arg = |i: usize, default: u32| numbers.get(i).copied().unwrap_or(default);
+
+        let (rules, max_args) = match kind {
+            "lives" => (
+                MatchRules::Lives {
+                    lives: arg(0, STARTING_LIFES as u32) as usize,
+                },
+                1,
+            ),
+            "points" => (
+                MatchRules::Points {
+                    to_win: arg(0, 11),
+                    win_by: arg(1, 2),
+                },
+                2,
+            ),
+            "sets" => (
+                MatchRules::Sets {
+                    best_of: arg(0, 3),
+                    to_win: arg(1, 11),
+                    win_by: arg(2, 2),
+                },
+                3,
+            ),
+            _ => {
+                return Err(format!(
+                    "unknown rules '{kind}', expected lives, points or sets"
+                ))
+            }
+        };
+        if numbers.len() > max_args {
+            return Err(format!("too many values for '{kind}' rules"));
+        }
+        if let MatchRules::Sets { best_of, .. } = rules {
+            if best_of % 2 == 0 {
+                return Err(format!(
+                    "best of {best_of} sets can end in a tie, use an odd number"
+                ));
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_need_the_lead_as_well_as_the_points() {
+        let rules: MatchRules = "points:11:2".parse().unwrap();
+        assert!(!rules.wins_game(11, 10));
+        assert!(rules.wins_game(12, 10));
+        assert!(rules.wins_game(11, 9));
+        assert!(!rules.wins_game(10, 0));
+    }
+
+    #[test]
+    fn even_number_of_sets_is_rejected() {
+        assert!("sets:4".parse::<MatchRules>().is_err());
+        assert!("sets:4:11:2".parse::<MatchRules>().is_err());
+        assert_eq!(
+            "sets:5".parse::<MatchRules>().unwrap().sets_to_win(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn presets_survive_their_spec() {
+        for rules in MatchRules::PRESETS {
+            assert_eq!(rules.spec().parse::<MatchRules>(), Ok(rules));
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use ratatui::symbols::Marker;
+
+use crate::constants::{BALL_SPEED, PADDLE_HEIGHT, STARTING_LIFES, TICK_RATE};
+use crate::controller::Keyboard;
+use crate::game::{GameConfig, Side};
+use crate::keymap::{key_name, Keymap};
+use crate::rules::MatchRules;
+use crate::theme::Theme;
+
+// The values the settings screen steps through
+const MAX_LIVES: usize = 9;
+const BALL_SPEEDS: [f64; 9] = [0.8, 1.0, 1.2, 1.4, 1.6, 1.8, 2.0, 2.2, 2.4];
+const PADDLE_HEIGHTS: [f64; 7] = [10.0, 15.0, 20.0, 25.0, 30.0, 35.0, 40.0];
+// Milliseconds per tick
+const TICK_RATES: [u64; 7] = [8, 10, 12, 16, 20, 25, 33];
+// `None` is whatever `detect_marker` picks
+const MARKERS: [Option<Marker>; 6] = [
+    None,
+    Some(Marker::Dot),
+    Some(Marker::Braille),
+    Some(Marker::HalfBlock),
+    Some(Marker::Block),
+    Some(Marker::Bar),
+];
+
+// What a config file may ask for, see `config`
+pub const BALL_SPEED_RANGE: (f64, f64) = (BALL_SPEEDS[0], BALL_SPEEDS[BALL_SPEEDS.len() - 1]);
+pub const PADDLE_SIZE_RANGE: (f64, f64) =
+    (PADDLE_HEIGHTS[0], PADDLE_HEIGHTS[PADDLE_HEIGHTS.len() - 1]);
+pub const TICK_RATE_RANGE: (u64, u64) = (TICK_RATES[0], TICK_RATES[TICK_RATES.len() - 1]);
+
+/// How a marker is written in the config file, `auto` for `None`.
+pub fn marker_name(marker: Option<Marker>) -> &'static str {
+    match marker {
+        None => "auto",
+        Some(Marker::Dot) => "dot",
+        Some(Marker::Braille) => "braille",
+        Some(Marker::HalfBlock) => "half-block",
+        Some(Marker::Block) => "block",
+        Some(Marker::Bar) => "bar",
+    }
+}
+
+pub fn parse_marker(s: &str) -> Result<Option<Marker>, String> {
+    MARKERS
+        .into_iter()
+        .find(|&marker| marker_name(marker) == s)
+        .ok_or_else(|| {
+            format!("unknown marker '{s}', expected auto, dot, braille, half-block, block or bar")
+        })
+}
+
+/// The finest marker this terminal can probably draw. Braille needs a UTF-8
+/// terminal and a font that has it, which the Linux console's doesn't. Half
+/// blocks are in just about every font, that one included.
+pub fn detect_marker() -> Marker {
+    static DETECTED: OnceLock<Marker> = OnceLock::new();
+    *DETECTED.get_or_init(|| {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        // the first of these that's set decides the character set
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .map(var)
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        let utf8 = locale.contains("utf-8") || locale.contains("utf8");
+        // Windows Terminal does UTF-8 without saying so in the locale
+        let windows_terminal = std::env::var_os("WT_SESSION").is_some();
+        if (utf8 || windows_terminal) && var("TERM") != "linux" {
+            Marker::Braille
+        } else {
+            Marker::HalfBlock
+        }
+    })
+}
+
+/// One line of the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Setting {
+    Rules,
+    Lives,
+    BallSpeed,
+    PaddleSize,
+    TickRate,
+    Marker,
+    Theme,
+    Keys,
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Setting::Rules => "Rules",
+            Setting::Lives => "Lifes",
+            Setting::BallSpeed => "Ball speed",
+            Setting::PaddleSize => "Paddle size",
+            Setting::TickRate => "Tick rate",
+            Setting::Marker => "Marker",
+            Setting::Theme => "Theme",
+            Setting::Keys => "Keys",
+        })
+    }
+}
+
+/// What the player picked on the settings screen. Anything that changes how
+/// a match plays out only takes effect when the next one starts, see `apply`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub rules: MatchRules,
+    /// Ball speed on serve, in units per tick.
+    pub ball_speed: f64,
+    pub paddle_height: f64,
+    pub tick_rate: Duration,
+    /// `None` leaves it to `detect_marker`, see `marker`.
+    pub marker: Option<Marker>,
+    pub theme: Theme,
+    /// The ones from the config file, offered after the built-in ones.
+    pub custom_themes: Vec<Theme>,
+    pub keys: Keymap,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            rules: MatchRules::default(),
+            ball_speed: BALL_SPEED,
+            paddle_height: PADDLE_HEIGHT,
+            tick_rate: TICK_RATE,
+            marker: None,
+            theme: Theme::classic(),
+            custom_themes: Vec::new(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Step `setting` to the next value, or the previous one for a negative
+    /// `step`. Numbers stop at either end of their range, the rest wrap around.
+    pub fn adjust(&mut self, setting: Setting, step: isize) {
+        match setting {
+            Setting::Rules => self.rules = cycle(&MatchRules::PRESETS, self.rules, step),
+            Setting::Lives => {
+                let lives = match self.rules {
+                    MatchRules::Lives { lives } => lives as isize + step,
+                    _ => STARTING_LIFES as isize,
+                };
+                self.rules = MatchRules::Lives {
+                    lives: lives.clamp(1, MAX_LIVES as isize) as usize,
+                };
+            }
+            Setting::BallSpeed => self.ball_speed = nudge(&BALL_SPEEDS, self.ball_speed, step),
+            Setting::PaddleSize => {
+                self.paddle_height = nudge(&PADDLE_HEIGHTS, self.paddle_height, step)
+            }
+            Setting::TickRate => {
+                let millis = TICK_RATES.map(|ms| ms as f64);
+                let current = self.tick_rate.as_millis() as f64;
+                self.tick_rate = Duration::from_millis(nudge(&millis, current, step) as u64);
+            }
+            Setting::Marker => self.marker = cycle(&MARKERS, self.marker, step),
+            Setting::Theme => {
+                let themes = self.themes();
+                let names: Vec<&str> = themes.iter().map(|theme| theme.name.as_str()).collect();
+                let name = cycle(&names, &self.theme.name, step);
+                if let Some(theme) = themes.iter().find(|theme| theme.name == name) {
+                    self.theme = theme.clone();
+                }
+            }
+            // rebound one by one on a screen of their own
+            Setting::Keys => {}
+        }
+    }
+
+    /// How `setting` is shown on the settings screen.
+    pub fn value(&self, setting: Setting) -> String {
+        match setting {
+            Setting::Rules => self.rules.to_string(),
+            Setting::Lives => match self.rules {
+                MatchRules::Lives { lives } => lives.to_string(),
+                _ => "-".to_string(),
+            },
+            Setting::BallSpeed => format!("{:.1}", self.ball_speed),
+            Setting::PaddleSize => format!("{}", self.paddle_height),
+            Setting::TickRate => format!("{} ms", self.tick_rate.as_millis()),
+            Setting::Marker => match self.marker {
+                Some(marker) => format!("{marker:?}"),
+                None => format!("Auto ({:?})", detect_marker()),
+            },
+            Setting::Theme => self.theme.name.clone(),
+            Setting::Keys => {
+                let (p1_up, p1_down) = self.keys.paddle_keys(Side::Left);
+                let (p2_up, p2_down) = self.keys.paddle_keys(Side::Right);
+                let clash = if self.keys.conflicts().is_empty() {
+                    ""
+                } else {
+                    " (clash!)"
+                };
+                format!(
+                    "P1 {}/{}, P2 {}/{}{clash}",
+                    key_name(p1_up),
+                    key_name(p1_down),
+                    key_name(p2_up),
+                    key_name(p2_down)
+                )
+            }
+        }
+    }
+
+    /// How the playground is drawn.
+    pub fn marker(&self) -> Marker {
+        self.marker.unwrap_or_else(detect_marker)
+    }
+
+    /// Pick the marker after the one in use, for switching while playing.
+    /// Going back to `auto` is left to the settings screen.
+    pub fn next_marker(&mut self) {
+        self.marker = cycle(&MARKERS[1..], Some(self.marker()), 1);
+    }
+
+    /// Every theme there is to pick from.
+    pub fn themes(&self) -> Vec<Theme> {
+        let mut themes = Theme::built_in();
+        themes.extend(self.custom_themes.iter().cloned());
+        themes
+    }
+
+    /// The theme called `name`, ignoring case.
+    pub fn theme_named(&self, name: &str) -> Result<Theme, String> {
+        let themes = self.themes();
+        themes
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<&str> = themes.iter().map(|theme| theme.name.as_str()).collect();
+                format!(
+                    "unknown theme '{name}', expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// Set up the next match the way these settings say.
+    pub fn apply(&self, config: &mut GameConfig) {
+        config.rules = self.rules;
+        config.ball_speed = self.ball_speed;
+        config.paddle_height = self.paddle_height;
+    }
+
+    /// A local player on `side`.
+    pub fn keyboard(&self, side: Side) -> Keyboard {
+        Keyboard::new(side, &self.keys)
+    }
+}
+
+// The option `step` places away from `current`, wrapping around. Anything
+// not in `options` starts over from the first one.
+fn cycle<T: Copy + PartialEq>(options: &[T], current: T, step: isize) -> T {
+    let index = options.iter().position(|&o| o == current).map_or(0, |i| {
+        (i as isize + step).rem_euclid(options.len() as isize) as usize
+    });
+    options[index]
+}
+
+// The value `step` places away from the one closest to `current`, stopping at
+// either end.
+fn nudge(options: &[f64], current: f64, step: isize) -> f64 {
+    let closest = (0..options.len())
+        .min_by(|&a, &b| {
+            (options[a] - current)
+                .abs()
+                .total_cmp(&(options[b] - current).abs())
+        })
+        .unwrap_or(0);
+    let index = (closest as isize + step).clamp(0, options.len() as isize - 1);
+    options[index as usize]
+}
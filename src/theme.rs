@@ -0,0 +1,226 @@
+use ratatui::style::Color;
+
+/// Every color the game draws with, apart from the red of error lines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// How it's written in the config file and shown in settings.
+    pub name: String,
+    pub p1: Color,
+    pub p2: Color,
+    pub ball: Color,
+    /// The frame around each screen.
+    pub border: Color,
+    /// Menu entries.
+    pub menu: Color,
+    /// The selected menu entry.
+    pub highlight: Color,
+    /// Key hints along the borders.
+    pub keys: Color,
+    /// Scores, the seed, debug info and other small print.
+    pub hud: Color,
+}
+
+impl Theme {
+    pub fn classic() -> Theme {
+        Theme {
+            name: "classic".to_string(),
+            p1: Color::Yellow,
+            p2: Color::Green,
+            ball: Color::Cyan,
+            border: Color::Reset,
+            menu: Color::Yellow,
+            highlight: Color::Yellow,
+            keys: Color::Blue,
+            hud: Color::DarkGray,
+        }
+    }
+
+    /// The ones that come with the game, `classic` first.
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::classic(),
+            Theme {
+                name: "ocean".to_string(),
+                p1: Color::LightBlue,
+                p2: Color::LightCyan,
+                ball: Color::White,
+                border: Color::Blue,
+                menu: Color::LightCyan,
+                highlight: Color::White,
+                keys: Color::LightBlue,
+                hud: Color::DarkGray,
+            },
+            Theme {
+                name: "ember".to_string(),
+                p1: Color::LightRed,
+                p2: Color::LightYellow,
+                ball: Color::LightMagenta,
+                border: Color::Red,
+                menu: Color::LightYellow,
+                highlight: Color::LightRed,
+                keys: Color::LightRed,
+                hud: Color::DarkGray,
+            },
+            Theme {
+                name: "mono".to_string(),
+                p1: Color::White,
+                p2: Color::White,
+                ball: Color::White,
+                border: Color::Gray,
+                menu: Color::Gray,
+                highlight: Color::White,
+                keys: Color::White,
+                hud: Color::Gray,
+            },
+            // nothing dim, so it all stands out from a black background
+            Theme {
+                name: "high-contrast".to_string(),
+                p1: Color::White,
+                p2: Color::LightYellow,
+                ball: Color::LightCyan,
+                border: Color::White,
+                menu: Color::White,
+                highlight: Color::LightYellow,
+                keys: Color::LightCyan,
+                hud: Color::White,
+            },
+            // the Okabe-Ito palette, which stays apart under the common
+            // kinds of color blindness
+            Theme {
+                name: "colorblind".to_string(),
+                p1: Color::Rgb(0xe6, 0x9f, 0x00),
+                p2: Color::Rgb(0x56, 0xb4, 0xe9),
+                ball: Color::Rgb(0xf0, 0xe4, 0x42),
+                border: Color::Reset,
+                menu: Color::Rgb(0x56, 0xb4, 0xe9),
+                highlight: Color::Rgb(0xe6, 0x9f, 0x00),
+                keys: Color::Rgb(0x00, 0x72, 0xb2),
+                hud: Color::Gray,
+            },
+        ]
+    }
+
+    /// The same theme with every color one `depth` can show.
+    pub fn downgrade(&self, depth: ColorDepth) -> Theme {
+        let fit = |color| depth.fit(color);
+        Theme {
+            name: self.name.clone(),
+            p1: fit(self.p1),
+            p2: fit(self.p2),
+            ball: fit(self.ball),
+            border: fit(self.border),
+            menu: fit(self.menu),
+            highlight: fit(self.highlight),
+            keys: fit(self.keys),
+            hud: fit(self.hud),
+        }
+    }
+}
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    /// Just the named ones.
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+// What xterm shows for the 16 named colors
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// Steps of the 6x6x6 color cube in the 256 color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// A guess from the environment. Terminals with 24-bit color mostly set
+    /// `COLORTERM`, 256 color ones say so in `TERM`.
+    pub fn detect() -> ColorDepth {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        let colorterm = var("COLORTERM");
+        if colorterm == "truecolor"
+            || colorterm == "24bit"
+            || std::env::var_os("WT_SESSION").is_some()
+        {
+            ColorDepth::TrueColor
+        } else if var("TERM").contains("256color") {
+            ColorDepth::Indexed256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// The closest to `color` this depth can show.
+    pub fn fit(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::TrueColor, _) => color,
+            (ColorDepth::Indexed256, Color::Rgb(r, g, b)) => Color::Indexed(to_indexed((r, g, b))),
+            (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => to_ansi((r, g, b)),
+            (ColorDepth::Ansi16, Color::Indexed(index)) => to_ansi(indexed_rgb(index)),
+            _ => color,
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).unsigned_abs().pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn to_ansi(rgb: (u8, u8, u8)) -> Color {
+    ANSI.into_iter()
+        .min_by_key(|&(_, ansi)| distance(rgb, ansi))
+        .map_or(Color::Reset, |(color, _)| color)
+}
+
+// The closer of the nearest cube color and the nearest gray
+fn to_indexed((r, g, b): (u8, u8, u8)) -> u8 {
+    let step = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| CUBE[i].abs_diff(v))
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * step(r) + 6 * step(g) + step(b);
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    if distance((r, g, b), indexed_rgb(gray)) < distance((r, g, b), indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI[index as usize].1,
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE[(i / 36) as usize],
+                CUBE[(i / 6 % 6) as usize],
+                CUBE[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}